@@ -0,0 +1,106 @@
+//! Compares reading a large configuration through the lock guarding it for the
+//! whole read with reading snapshots of it, which do not acquire the lock once
+//! the current version has been kept apart, while a writer keeps updating it.
+//!
+//! Run with `cargo bench`. This benchmark only uses the standard library, so
+//! it reports the average time per read measured over a fixed number of reads.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use protected_smart_pointer::{Permissions, Protected};
+
+const READERS: u32 = 4;
+const READS: u32 = 200_000;
+const ENTRIES: usize = 10_000;
+
+type Config = HashMap<String, String>;
+
+fn config() -> Config {
+    (0..ENTRIES)
+        .map(|i| (format!("key-{i}"), format!("value-{i}")))
+        .collect()
+}
+
+/// Updates every entry, as reloading the configuration would.
+fn update(config: &mut Config, version: usize) {
+    for (i, value) in config.values_mut().enumerate() {
+        *value = format!("value-{version}-{i}");
+    }
+}
+
+/// Runs `READERS` threads calling `read` `READS` times each while `write` is
+/// called in a loop, and returns the average time per read along with the
+/// number of writes completed in the meantime.
+fn run(read: impl Fn(u32) + Sync, write: impl Fn(usize) + Sync) -> (Duration, usize) {
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        let writer = s.spawn(|| {
+            let mut version = 0;
+            while !done.load(Ordering::Relaxed) {
+                version += 1;
+                write(version);
+            }
+            version
+        });
+        let readers: Vec<_> = (0..READERS)
+            .map(|reader| {
+                let read = &read;
+                s.spawn(move || {
+                    let start = Instant::now();
+                    for _ in 0..READS {
+                        read(reader);
+                    }
+                    start.elapsed()
+                })
+            })
+            .collect();
+        let elapsed: Duration = readers.into_iter().map(|r| r.join().unwrap()).sum();
+        done.store(true, Ordering::Relaxed);
+        (elapsed / (READERS * READS), writer.join().unwrap())
+    })
+}
+
+fn main() {
+    let owner = Protected::new(config());
+    let users: Vec<_> = (0..READERS)
+        .map(|id| owner.create_read_only_user(id).unwrap())
+        .collect();
+    let writer = owner.create_user(READERS, Permissions::ReadWrite).unwrap();
+    let (lock, writes) = run(
+        |reader| {
+            let config = users[reader as usize].read().unwrap();
+            black_box(config.get("key-42"));
+        },
+        |version| {
+            update(&mut writer.write().unwrap(), version);
+        },
+    );
+    println!("lock:     {lock:?} per read, {writes} writes");
+
+    let owner = Protected::new(Arc::new(config()));
+    let users: Vec<_> = (0..READERS)
+        .map(|id| owner.create_read_only_user(id).unwrap())
+        .collect();
+    let writer = owner.create_user(READERS, Permissions::ReadWrite).unwrap();
+    let (swap, writes) = run(
+        |reader| {
+            let config = users[reader as usize].snapshot().unwrap();
+            black_box(config.get("key-42"));
+        },
+        |version| {
+            writer
+                .update(|config| {
+                    let mut config = config.clone();
+                    update(&mut config, version);
+                    config
+                })
+                .unwrap();
+        },
+    );
+    println!("arc swap: {swap:?} per read, {writes} writes");
+}
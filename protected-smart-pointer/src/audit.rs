@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError, RwLock};
+use std::time::SystemTime;
+
+use crate::{AccessError, Operation, Owner, Permissions, Protected, UserId};
+
+/// Something that happened to a `Protected<T>`, as reported to audit observers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEvent<Id = u32> {
+    /// When the event happened.
+    pub timestamp: SystemTime,
+    /// What happened.
+    pub kind: AuditEventKind<Id>,
+}
+
+/// The kinds of events reported to audit observers.
+///
+/// Events caused by the owner of `T` carry no user ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AuditEventKind<Id = u32> {
+    /// The owner granted access to `T` to a new user.
+    UserCreated { id: Id, permissions: Permissions },
+    /// A user shared its access to `T` with a new sub-user.
+    UserDelegated {
+        id: Id,
+        parent: Id,
+        permissions: Permissions,
+    },
+    /// The owner revoked the access of a user, or the user that delegated its
+    /// access to it was revoked or dropped.
+    UserRemoved { id: Id },
+    /// A user was dropped, giving up its own access to `T`.
+    UserDropped { id: Id },
+    /// The owner downgraded a user to a weak handle, which does not keep `T` alive.
+    UserDowngraded { id: Id },
+    /// The owner suspended the access of a user.
+    UserSuspended { id: Id },
+    /// The owner resumed the access of a suspended user.
+    UserResumed { id: Id },
+    /// The owner suspended the access of every user.
+    UsersSuspended,
+    /// The owner resumed the access of every user.
+    UsersResumed,
+    /// The owner granted permissions to a group of users.
+    GroupGranted {
+        group: String,
+        permissions: Permissions,
+    },
+    /// The owner revoked the access of a group of users.
+    GroupRevoked { group: String },
+    /// The owner added a user to a group.
+    AddedToGroup { id: Id, group: String },
+    /// The owner removed a user from a group.
+    RemovedFromGroup { id: Id, group: String },
+    /// An owner created a co-owner, possibly to hand its ownership over.
+    CoOwnerCreated,
+    /// An owner was dropped while other owners remain.
+    CoOwnerDropped,
+    /// An owner destroyed `T`, revoking every user.
+    Destroyed,
+    /// The last owner was dropped, revoking every user.
+    OwnerDropped,
+    /// A guard to `T` was handed out.
+    Granted {
+        id: Option<Id>,
+        operation: Operation,
+    },
+    /// A user was denied access to `T`.
+    Denied {
+        id: Id,
+        operation: Operation,
+        error: AccessError<Id>,
+    },
+}
+
+/// Receives the events happening to a `Protected<T>`.
+///
+/// Observers are called synchronously, possibly while the lock guarding `T` is held,
+/// so they must not access the `Protected<T>` they are observing.
+///
+/// This trait is implemented for every `Fn(&AuditEvent<Id>)` closure.
+pub trait AuditObserver<Id = u32>: Send + Sync {
+    /// Called every time something happens to the observed `Protected<T>`.
+    fn on_event(&self, event: &AuditEvent<Id>);
+}
+
+impl<Id, F: Fn(&AuditEvent<Id>) + Send + Sync> AuditObserver<Id> for F {
+    fn on_event(&self, event: &AuditEvent<Id>) {
+        self(event)
+    }
+}
+
+/// Dispatches events to the observers and the audit log of a `Protected<T>`.
+pub(crate) struct Audit<Id> {
+    /// Set when there is at least one observer or the audit log is enabled,
+    /// so that nothing else has to be touched when auditing is off.
+    enabled: AtomicBool,
+    observers: RwLock<Vec<Box<dyn AuditObserver<Id>>>>,
+    log: Mutex<AuditLog<Id>>,
+}
+
+/// Ring buffer holding the most recent events.
+struct AuditLog<Id> {
+    capacity: usize,
+    events: VecDeque<AuditEvent<Id>>,
+}
+
+impl<Id> Audit<Id> {
+    pub(crate) fn new() -> Audit<Id> {
+        Audit {
+            enabled: AtomicBool::new(false),
+            observers: RwLock::new(Vec::new()),
+            log: Mutex::new(AuditLog {
+                capacity: 0,
+                events: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Reports an event, if anybody is listening.
+    ///
+    /// `kind` is only evaluated when auditing is enabled.
+    pub(crate) fn record(&self, kind: impl FnOnce() -> AuditEventKind<Id>) {
+        if !self.enabled.load(Ordering::Acquire) {
+            return;
+        }
+
+        let event = AuditEvent {
+            timestamp: SystemTime::now(),
+            kind: kind(),
+        };
+        let observers = self
+            .observers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        for observer in observers.iter() {
+            observer.on_event(&event);
+        }
+
+        let mut log = self.log.lock().unwrap_or_else(PoisonError::into_inner);
+        if log.capacity > 0 {
+            if log.events.len() == log.capacity {
+                log.events.pop_front();
+            }
+            log.events.push_back(event);
+        }
+    }
+
+    fn update_enabled(&self, observers: &[Box<dyn AuditObserver<Id>>], log: &AuditLog<Id>) {
+        let enabled = !observers.is_empty() || log.capacity > 0;
+        self.enabled.store(enabled, Ordering::Release);
+    }
+}
+
+impl<T, Id: UserId> Protected<T, Owner, Id> {
+    /// Registers an observer that is called every time a user is created or
+    /// removed, the owner is dropped, or a guard to `T` is requested.
+    ///
+    /// See [`AuditObserver`] for the restrictions that apply to observers.
+    pub fn add_observer(&self, observer: impl AuditObserver<Id> + 'static) {
+        let audit = &self.inner.audit;
+        let mut observers = audit
+            .observers
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        observers.push(Box::new(observer));
+        let log = audit.log.lock().unwrap_or_else(PoisonError::into_inner);
+        audit.update_enabled(&observers, &log);
+    }
+
+    /// Keeps the `capacity` most recent audit events in memory, so that they
+    /// can be retrieved with [`Protected::audit_log`].
+    ///
+    /// Events that no longer fit are discarded, oldest first. A `capacity` of zero
+    /// disables the audit log, which is the default.
+    pub fn set_audit_log_capacity(&self, capacity: usize) {
+        let audit = &self.inner.audit;
+        let observers = audit
+            .observers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut log = audit.log.lock().unwrap_or_else(PoisonError::into_inner);
+        log.capacity = capacity;
+        while log.events.len() > capacity {
+            log.events.pop_front();
+        }
+        log.events.shrink_to(capacity);
+        audit.update_enabled(&observers, &log);
+    }
+
+    /// Returns the audit events kept in memory, oldest first.
+    pub fn audit_log(&self) -> Vec<AuditEvent<Id>> {
+        let log = self
+            .inner
+            .audit
+            .log
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        log.events.iter().cloned().collect()
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, RwLock};
+
+use crate::audit::Audit;
+use crate::lock::Lock;
+use crate::notify::ReleaseNotifier;
+use crate::slot::Slot;
+use crate::stats::Registry;
+use crate::{AccessControl, Clock, Owner, Protected, ProtectedBox, Shared, SystemClock, UserId};
+
+/// What happens to the users of `T`, and to `T` itself, once the last owner
+/// of `T` has been dropped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OwnerDropPolicy {
+    /// Every user is revoked, and `T` is dropped along with the last user.
+    #[default]
+    RevokeUsers,
+    /// Every user is revoked, and `T` is dropped right away, which suits values
+    /// holding resources such as file handles or sockets.
+    ///
+    /// Users are then denied access with [`AccessError::Destroyed`](crate::AccessError::Destroyed).
+    DestroyValue,
+    /// Users keep their access to `T`, and `T` is dropped along with the last user.
+    KeepUsers,
+}
+
+/// Configures a `Protected<T>` before creating its owner.
+///
+/// This builder is returned by [`Protected::builder`].
+pub struct ProtectedBuilder<T, Id = u32> {
+    value: T,
+    clock: Box<dyn Clock>,
+    owner_drop_policy: OwnerDropPolicy,
+    zeroize: Option<fn(&mut T)>,
+    _ids: PhantomData<fn() -> Id>,
+}
+
+impl<T> Protected<T, Owner> {
+    /// Returns a builder to configure a `Protected` access to `T`.
+    ///
+    /// Unless configured otherwise, users are identified by `u32`, leases are
+    /// measured with a [`SystemClock`], and users are revoked when the last owner
+    /// is dropped.
+    pub fn builder(value: T) -> ProtectedBuilder<T> {
+        ProtectedBuilder {
+            value,
+            clock: Box::new(SystemClock),
+            owner_drop_policy: OwnerDropPolicy::default(),
+            zeroize: None,
+            _ids: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "secret")]
+impl<T: zeroize::Zeroize, Id: UserId> ProtectedBuilder<T, Id> {
+    /// Treats `T` as a secret, such as key material.
+    ///
+    /// `T` is wiped with [`Zeroize`](zeroize::Zeroize) as soon as it is destroyed,
+    /// either with [`Protected::destroy`], or when the last owner is dropped:
+    /// secrets are always destroyed along with their last owner, as with
+    /// [`OwnerDropPolicy::DestroyValue`], whatever the owner drop policy.
+    /// `T` is also wiped if it is dropped in any other way. Guards to `T` do not
+    /// show its value in their `Debug` output.
+    pub fn secret(mut self) -> ProtectedBuilder<T, Id> {
+        self.zeroize = Some(|value| value.zeroize());
+        self
+    }
+}
+
+impl<T, Id: UserId> ProtectedBuilder<T, Id> {
+    /// Uses `clock` to decide when the leases granted to users expire.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> ProtectedBuilder<T, Id> {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets what happens once the last owner of `T` has been dropped.
+    ///
+    /// Secret values are destroyed once the last owner has been dropped,
+    /// whatever this policy.
+    pub fn owner_drop_policy(mut self, policy: OwnerDropPolicy) -> ProtectedBuilder<T, Id> {
+        self.owner_drop_policy = policy;
+        self
+    }
+
+    /// Identifies users by `NewId` instead.
+    pub fn user_ids<NewId: UserId>(self) -> ProtectedBuilder<T, NewId> {
+        ProtectedBuilder {
+            value: self.value,
+            clock: self.clock,
+            owner_drop_policy: self.owner_drop_policy,
+            zeroize: self.zeroize,
+            _ids: PhantomData,
+        }
+    }
+
+    /// Creates a `Protected` access to `T`.
+    ///
+    /// The instance returned by this function is considered the _owner_ of `T`.
+    pub fn build(self) -> Protected<T, Owner, Id> {
+        // Secrets are destroyed along with their last owner, so that they cannot
+        // be left behind.
+        let owner_drop_policy = match self.zeroize {
+            Some(_) => OwnerDropPolicy::DestroyValue,
+            None => self.owner_drop_policy,
+        };
+        let inner = Arc::new(Shared {
+            lock: Lock::new(ProtectedBox {
+                value: Some(self.value),
+                zeroize: self.zeroize,
+            }),
+            control: RwLock::new(AccessControl {
+                access_keys: HashMap::new(),
+                groups: HashMap::new(),
+                next_generation: 0,
+                next_anonymous_id: Some(0),
+                clock: self.clock,
+                owners: 1,
+                owner_drop_policy,
+                users_suspended: false,
+                destroyed: false,
+            }),
+            version: AtomicU64::new(0),
+            snapshot: Slot::new(),
+            released: ReleaseNotifier::new(),
+            audit: Audit::new(),
+            stats: Registry::new(),
+        });
+
+        Protected {
+            counters: inner.stats.owner(),
+            inner,
+            capability: None,
+            holders: None,
+            _marker: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Source of time used by `Protected<T>` to decide whether an access lease
+/// has expired.
+pub trait Clock: Send + Sync {
+    /// Returns the current instant according to this clock.
+    fn now(&self) -> Instant;
+}
+
+/// Clock backed by [`Instant::now`].
+///
+/// This is the clock used by [`Protected::new`](crate::Protected::new).
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves forward when told to.
+///
+/// Clones of a `ManualClock` share the same time, so a clone can be handed
+/// to [`Protected::with_clock`](crate::Protected::with_clock) while the
+/// original is used to advance it.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Creates a clock frozen at the current instant.
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Moves this clock (and all of its clones) forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
@@ -0,0 +1,154 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::Permissions;
+
+/// Operation that a handle attempted to perform on `T`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Shared read access to `T`.
+    Read,
+    /// Exclusive write access to `T`.
+    Write,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Read => f.write_str("read"),
+            Operation::Write => f.write_str("write"),
+        }
+    }
+}
+
+/// Indicates why a handle to `T` could not perform the requested operation.
+///
+/// Every variant carries the ID of the user that was denied, if any.
+/// Errors reported to the owner of `T` carry no ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AccessError<Id = u32> {
+    /// Every owner of `T` has been dropped, which revoked every user.
+    OwnerDropped { id: Id },
+    /// The value has been destroyed, as the owner drop policy required once
+    /// every owner was dropped, or because only weak handles to it were left.
+    Destroyed { id: Option<Id> },
+    /// The owner has revoked the access of this user.
+    Revoked { id: Id },
+    /// The lease granted to this user has run out.
+    Expired { id: Id },
+    /// The owner has suspended the access of this user, of a user it was delegated
+    /// access from, or of every user, until it is resumed.
+    Suspended { id: Id },
+    /// The groups of the user, or of a user it was delegated access from,
+    /// do not grant it any permission.
+    GroupsDenied { id: Id },
+    /// The user has access to `T`, but its permissions do not allow the
+    /// requested operation.
+    PermissionDenied {
+        id: Id,
+        permissions: Permissions,
+        operation: Operation,
+    },
+    /// The user attempted to delegate permissions that it does not have.
+    PermissionsExceeded {
+        id: Id,
+        permissions: Permissions,
+        requested: Permissions,
+    },
+    /// A user with this ID already exists.
+    UserExists { id: Id },
+    /// Every ID that can be allocated automatically has already been handed out.
+    IdsExhausted,
+    /// The lock guarding `T` is currently held in a way that prevents the
+    /// requested operation, and blocking was not allowed.
+    WouldBlock { id: Option<Id> },
+    /// The lock guarding `T` could not be acquired before the timeout elapsed.
+    TimedOut { id: Option<Id> },
+    /// The lock guarding `T` was poisoned by a thread that panicked while
+    /// holding it.
+    Poisoned { id: Option<Id> },
+}
+
+impl<Id: Clone> AccessError<Id> {
+    /// Returns the ID of the user that was denied, or `None` if the error
+    /// was reported to the owner of `T`.
+    pub fn id(&self) -> Option<Id> {
+        match self {
+            AccessError::OwnerDropped { id }
+            | AccessError::Revoked { id }
+            | AccessError::Expired { id }
+            | AccessError::Suspended { id }
+            | AccessError::GroupsDenied { id }
+            | AccessError::PermissionDenied { id, .. }
+            | AccessError::PermissionsExceeded { id, .. }
+            | AccessError::UserExists { id } => Some(id.clone()),
+            AccessError::Destroyed { id }
+            | AccessError::WouldBlock { id }
+            | AccessError::TimedOut { id }
+            | AccessError::Poisoned { id } => id.clone(),
+            AccessError::IdsExhausted => None,
+        }
+    }
+}
+
+impl<Id: fmt::Display> fmt::Display for AccessError<Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccessError::OwnerDropped { id } => {
+                write!(f, "user {id} lost its access because the owner was dropped")
+            }
+            AccessError::Destroyed { id: Some(id) } => {
+                write!(f, "the value user {id} had access to has been destroyed")
+            }
+            AccessError::Destroyed { id: None } => f.write_str("the value has been destroyed"),
+            AccessError::Revoked { id } => write!(f, "user {id} has been revoked"),
+            AccessError::Expired { id } => write!(f, "the lease of user {id} has expired"),
+            AccessError::Suspended { id } => write!(f, "user {id} has been suspended"),
+            AccessError::GroupsDenied { id } => {
+                write!(f, "user {id} is not granted any permission by its groups")
+            }
+            AccessError::PermissionDenied {
+                id,
+                permissions,
+                operation,
+            } => write!(
+                f,
+                "user {id} is not allowed to {operation} with {permissions:?} permissions"
+            ),
+            AccessError::PermissionsExceeded {
+                id,
+                permissions,
+                requested,
+            } => write!(
+                f,
+                "user {id} cannot delegate {requested:?} permissions with {permissions:?} permissions"
+            ),
+            AccessError::UserExists { id } => write!(f, "user {id} already exists"),
+            AccessError::IdsExhausted => f.write_str("no user ID is left to allocate"),
+            AccessError::WouldBlock { id: Some(id) } => {
+                write!(f, "user {id} would have to block to acquire the lock")
+            }
+            AccessError::WouldBlock { id: None } => {
+                f.write_str("the owner would have to block to acquire the lock")
+            }
+            AccessError::TimedOut { id: Some(id) } => {
+                write!(f, "user {id} timed out while waiting for the lock")
+            }
+            AccessError::TimedOut { id: None } => {
+                f.write_str("the owner timed out while waiting for the lock")
+            }
+            AccessError::Poisoned { id: Some(id) } => {
+                write!(
+                    f,
+                    "user {id} cannot access the value because its lock is poisoned"
+                )
+            }
+            AccessError::Poisoned { id: None } => {
+                f.write_str("the owner cannot access the value because its lock is poisoned")
+            }
+        }
+    }
+}
+
+impl<Id: fmt::Debug + fmt::Display> Error for AccessError<Id> {}
@@ -0,0 +1,276 @@
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::notify::ReleaseNotifier;
+use crate::{
+    AccessError, Owner, Protected, ProtectedReadGuard, ProtectedWriteGuard, User, UserAccess,
+    UserId, Wait,
+};
+
+/// Future that resolves to shared read access to `T`.
+///
+/// This future is returned by `read_async`. Access is checked when the lock
+/// is acquired, not when the future is created. The guard it resolves to is
+/// `Send`, so it may be held across `.await` points in tasks that are spawned
+/// onto multi-threaded executors.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ReadFuture<'a, T, A, Id: UserId = u32> {
+    protected: &'a Protected<T, A, Id>,
+}
+
+/// Future that resolves to exclusive write access to `T`.
+///
+/// This future is returned by `write_async`. Access is checked when the lock
+/// is acquired, not when the future is created. The guard it resolves to is
+/// `Send`, like the one of [`ReadFuture`].
+///
+/// Once this future has been polled without resolving, readers wait for it to
+/// resolve or be dropped, as they do for threads blocked in `write`, so that
+/// it cannot be starved by a steady stream of readers.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WriteFuture<'a, T, A, Id: UserId = u32> {
+    protected: &'a Protected<T, A, Id>,
+    /// Whether this future is counted as a writer waiting for the lock.
+    waiting: bool,
+}
+
+impl<T, Id: UserId> Protected<T, Owner, Id> {
+    /// Locks this `T` so that the owner has shared read access to `T`, without
+    /// blocking the current thread while waiting for the lock.
+    ///
+    /// Dropping the returned guard wakes up the tasks waiting for the lock.
+    ///
+    /// # Errors
+    ///
+    /// The future resolves to an error if the lock guarding `T` has been poisoned.
+    pub fn read_async(&self) -> ReadFuture<'_, T, Owner, Id> {
+        ReadFuture { protected: self }
+    }
+
+    /// Locks this `T` so that the owner has exclusive write access to `T`, without
+    /// blocking the current thread while waiting for the lock.
+    ///
+    /// Dropping the returned guard wakes up the tasks waiting for the lock.
+    ///
+    /// # Errors
+    ///
+    /// The future resolves to an error if the lock guarding `T` has been poisoned.
+    pub fn write_async(&self) -> WriteFuture<'_, T, Owner, Id> {
+        WriteFuture {
+            protected: self,
+            waiting: false,
+        }
+    }
+}
+
+impl<T, A: UserAccess, Id: UserId> Protected<T, A, Id> {
+    /// Locks this `T` so that this user has shared read access to `T`, without
+    /// blocking the current thread while waiting for the lock.
+    ///
+    /// Dropping the returned guard wakes up the tasks waiting for the lock.
+    ///
+    /// # Errors
+    ///
+    /// The future resolves to any of the errors returned by [`read`](Protected::read)
+    /// if this user is denied access to `T` by the time the lock is acquired.
+    pub fn read_async(&self) -> ReadFuture<'_, T, A, Id> {
+        ReadFuture { protected: self }
+    }
+}
+
+impl<T, Id: UserId> Protected<T, User, Id> {
+    /// Locks this `T` so that this user has exclusive write access to `T`, without
+    /// blocking the current thread while waiting for the lock.
+    ///
+    /// Dropping the returned guard wakes up the tasks waiting for the lock.
+    ///
+    /// # Errors
+    ///
+    /// The future resolves to any of the errors returned by [`write`](Protected::write)
+    /// if this user is denied access to `T` by the time the lock is acquired.
+    pub fn write_async(&self) -> WriteFuture<'_, T, User, Id> {
+        WriteFuture {
+            protected: self,
+            waiting: false,
+        }
+    }
+}
+
+impl<'a, T, A, Id: UserId> Future for ReadFuture<'a, T, A, Id> {
+    type Output = Result<ProtectedReadGuard<'a, T, Id>, AccessError<Id>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let protected = self.protected;
+        poll_lock(&protected.inner.released, cx, || {
+            protected.read_checked(Wait::Never)
+        })
+    }
+}
+
+impl<'a, T, A, Id: UserId> Future for WriteFuture<'a, T, A, Id> {
+    type Output = Result<ProtectedWriteGuard<'a, T, Id>, AccessError<Id>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let protected = self.protected;
+        let poll = poll_lock(&protected.inner.released, cx, || {
+            protected.write_checked(Wait::Never)
+        });
+        match &poll {
+            Poll::Pending if !self.waiting => {
+                protected.inner.lock.start_waiting_to_write();
+                self.waiting = true;
+            }
+            Poll::Pending => {}
+            Poll::Ready(result) => {
+                if mem::take(&mut self.waiting) {
+                    protected.inner.lock.stop_waiting_to_write();
+                    if result.is_err() {
+                        protected.inner.released.notify();
+                    }
+                }
+            }
+        }
+        poll
+    }
+}
+
+impl<T, A, Id: UserId> Drop for WriteFuture<'_, T, A, Id> {
+    fn drop(&mut self) {
+        if self.waiting {
+            // Readers may have been waiting for this future only.
+            self.protected.inner.lock.stop_waiting_to_write();
+            self.protected.inner.released.notify();
+        }
+    }
+}
+
+/// Attempts to acquire the lock, registering the task to be woken up when
+/// a guard is released if the lock is not available.
+fn poll_lock<G, Id>(
+    released: &ReleaseNotifier,
+    cx: &mut Context<'_>,
+    attempt: impl Fn() -> Result<G, AccessError<Id>>,
+) -> Poll<Result<G, AccessError<Id>>> {
+    match attempt() {
+        Err(AccessError::WouldBlock { .. }) => {}
+        result => return Poll::Ready(result),
+    }
+
+    released.register(cx.waker());
+
+    // The lock may have been released before the waker was registered.
+    match attempt() {
+        Err(AccessError::WouldBlock { .. }) => Poll::Pending,
+        result => Poll::Ready(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use std::thread::{self, Thread};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Permissions;
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Minimal executor that parks the current thread until the future is woken up.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn owner_can_read_and_write_async() {
+        let owner = Protected::new(42);
+        *block_on(owner.write_async()).unwrap() = 43;
+        assert_eq!(*block_on(owner.read_async()).unwrap(), 43);
+    }
+
+    #[test]
+    fn user_can_read_and_write_async() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        *block_on(user.write_async()).unwrap() = 43;
+        assert_eq!(*block_on(user.read_async()).unwrap(), 43);
+    }
+
+    #[test]
+    fn pending_read_resolves_once_write_guard_is_dropped() {
+        let owner = Protected::new(42);
+        let user = owner.create_read_only_user(0).unwrap();
+        thread::scope(|scope| {
+            let mut x = owner.write().unwrap();
+            let reader = scope.spawn(|| *block_on(user.read_async()).unwrap());
+            thread::sleep(Duration::from_millis(50));
+            *x = 43;
+            drop(x);
+            assert_eq!(reader.join().unwrap(), 43);
+        });
+    }
+
+    fn assert_send<F: Future + Send>(future: F) -> F {
+        future
+    }
+
+    #[test]
+    fn guards_can_be_held_across_await_points_in_send_futures() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        block_on(assert_send(async {
+            let mut x = user.write_async().await.unwrap();
+            std::future::ready(()).await;
+            *x += 1;
+        }));
+        assert_eq!(*owner.read().unwrap(), 43);
+    }
+
+    #[test]
+    fn pending_writes_hold_readers_back() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        let x = owner.read().unwrap();
+        let mut future = user.write_async();
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert_eq!(
+            owner.try_read().err(),
+            Some(AccessError::WouldBlock { id: None })
+        );
+        drop(future);
+        assert!(owner.try_read().is_ok());
+
+        let mut future = user.write_async();
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        drop(x);
+        *block_on(future).unwrap() += 1;
+        assert_eq!(*owner.try_read().unwrap(), 43);
+    }
+
+    #[test]
+    fn access_is_checked_when_the_future_resolves() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let future = user.write_async();
+        owner.remove_user(0).unwrap();
+        assert_eq!(block_on(future).err(), Some(AccessError::Revoked { id: 0 }));
+    }
+}
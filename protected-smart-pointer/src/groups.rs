@@ -0,0 +1,131 @@
+use crate::{AccessError, AuditEventKind, Owner, Permissions, Protected, UserId};
+
+impl<T, Id: UserId> Protected<T, Owner, Id> {
+    /// Grants `permissions` to the group with a given name, creating the group
+    /// if it does not exist yet.
+    ///
+    /// A user that belongs to at least one group is granted the permissions of
+    /// all its groups combined, but never more than the permissions it was created
+    /// with. Users that belong to no group keep the permissions they were created with.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn grant_group(
+        &self,
+        group: &str,
+        permissions: Permissions,
+    ) -> Result<(), AccessError<Id>> {
+        let mut control = self.write_control()?;
+        control.groups.insert(group.to_owned(), Some(permissions));
+        self.inner.audit.record(|| AuditEventKind::GroupGranted {
+            group: group.to_owned(),
+            permissions,
+        });
+        Ok(())
+    }
+
+    /// Revokes the access of the group with a given name.
+    ///
+    /// Once this function returns, the members of the group that belong to no
+    /// other group granting them access cannot obtain any new guard to `T`.
+    /// The group keeps its members, and can be granted access again with
+    /// [`Protected::grant_group`]. Returns `false` if there is no such group.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn revoke_group(&self, group: &str) -> Result<bool, AccessError<Id>> {
+        let mut control = self.write_control()?;
+        let Some(permissions) = control.groups.get_mut(group) else {
+            return Ok(false);
+        };
+        *permissions = None;
+        self.inner.audit.record(|| AuditEventKind::GroupRevoked {
+            group: group.to_owned(),
+        });
+        Ok(true)
+    }
+
+    /// Adds the user with a given ID to the group with a given name.
+    ///
+    /// The user leaves its groups when it is revoked or dropped. Returns `false`
+    /// if there is no such user or group.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn add_to_group(&self, id: Id, group: &str) -> Result<bool, AccessError<Id>> {
+        let mut control = self.write_control()?;
+        let control = &mut *control;
+        let Some(access_key) = control.access_keys.get_mut(&id) else {
+            return Ok(false);
+        };
+        if !control.groups.contains_key(group) {
+            return Ok(false);
+        }
+        if access_key.groups.insert(group.to_owned()) {
+            self.inner.audit.record(|| AuditEventKind::AddedToGroup {
+                id,
+                group: group.to_owned(),
+            });
+        }
+        Ok(true)
+    }
+
+    /// Removes the user with a given ID from the group with a given name.
+    ///
+    /// A user removed from its last group is granted the permissions it was
+    /// created with again. Returns `false` if the user did not belong to the group.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn remove_from_group(&self, id: Id, group: &str) -> Result<bool, AccessError<Id>> {
+        let mut control = self.write_control()?;
+        let removed = control
+            .access_keys
+            .get_mut(&id)
+            .is_some_and(|access_key| access_key.groups.remove(group));
+        if removed {
+            self.inner
+                .audit
+                .record(|| AuditEventKind::RemovedFromGroup {
+                    id,
+                    group: group.to_owned(),
+                });
+        }
+        Ok(removed)
+    }
+
+    /// Returns the names of the groups the user with a given ID belongs to,
+    /// in no particular order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn groups_of(&self, id: Id) -> Result<Vec<String>, AccessError<Id>> {
+        let control = self.read_control()?;
+        Ok(control
+            .access_keys
+            .get(&id)
+            .map(|access_key| access_key.groups.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// Returns the IDs of the members of the group with a given name,
+    /// in no particular order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn members_of(&self, group: &str) -> Result<Vec<Id>, AccessError<Id>> {
+        let control = self.read_control()?;
+        Ok(control
+            .access_keys
+            .iter()
+            .filter(|(_, access_key)| access_key.groups.contains(group))
+            .map(|(id, _)| id.clone())
+            .collect())
+    }
+}
@@ -1,10 +1,52 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::sync::RwLock;
-use std::sync::RwLockReadGuard;
-use std::sync::RwLockWriteGuard;
+use std::sync::TryLockError;
+use std::sync::TryLockResult;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+
+mod audit;
+mod builder;
+mod clock;
+mod error;
+#[cfg(feature = "async")]
+mod future;
+mod groups;
+mod lock;
+mod map;
+mod notify;
+mod slot;
+mod stats;
+mod suspend;
+mod swap;
+mod upgrade;
+mod view;
+mod watch;
+mod weak;
+
+use audit::Audit;
+use lock::{Lock, ReadGuard, WriteGuard};
+use notify::{Release, ReleaseNotifier};
+use slot::Slot;
+use stats::{Registry, SharedCounters, Stopwatch};
+
+pub use audit::{AuditEvent, AuditEventKind, AuditObserver};
+pub use builder::{OwnerDropPolicy, ProtectedBuilder};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use error::{AccessError, Operation};
+#[cfg(feature = "async")]
+pub use future::{ReadFuture, WriteFuture};
+pub use map::{MappedProtectedReadGuard, MappedProtectedWriteGuard};
+#[cfg(feature = "stats")]
+pub use stats::{AccessStats, ProtectedStats};
+pub use upgrade::ProtectedUpgradableReadGuard;
+pub use view::ProtectedView;
+pub use weak::WeakProtected;
 
 /// Zero-sized type used to mark instances of `Protected<T>` that
 /// "own" the `T` in the sense that they manage access to it.
@@ -14,285 +56,3366 @@ pub struct Owner;
 /// may have access to `T` as long as the owner allows it.
 pub struct User;
 
-/// Indicates that the user no longer has access to `T`.
-#[derive(Debug)]
-pub struct AccessDeniedError;
+/// Zero-sized type used to mark instances of `Protected<T>` that
+/// may have read-only access to `T` as long as the owner allows it.
+///
+/// Unlike [`User`], these instances do not even provide a `write` method.
+pub struct ReadOnlyUser;
+
+/// Marker trait implemented by the access types of the users of `T`.
+///
+/// This trait is sealed and cannot be implemented outside of this crate.
+pub trait UserAccess: sealed::Sealed {}
+
+impl UserAccess for User {}
+impl UserAccess for ReadOnlyUser {}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::User {}
+    impl Sealed for super::ReadOnlyUser {}
+}
+
+/// Type used to identify the users of `T`.
+///
+/// This trait is implemented for every type that can be used as a key in a
+/// [`HashMap`], such as `u32`, which is the default, `String`, or a UUID.
+pub trait UserId: Hash + Eq + Clone {}
+
+impl<Id: Hash + Eq + Clone> UserId for Id {}
+
+/// Set of operations that the owner allows a user to perform on `T`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permissions {
+    /// The user may only read `T`.
+    ReadOnly,
+    /// The user may only write `T`.
+    WriteOnly,
+    /// The user may both read and write `T`.
+    ReadWrite,
+}
+
+impl Permissions {
+    /// Returns `true` if these permissions allow reading `T`.
+    pub fn can_read(self) -> bool {
+        matches!(self, Permissions::ReadOnly | Permissions::ReadWrite)
+    }
+
+    /// Returns `true` if these permissions allow writing `T`.
+    pub fn can_write(self) -> bool {
+        matches!(self, Permissions::WriteOnly | Permissions::ReadWrite)
+    }
+
+    /// Returns `true` if these permissions allow every operation allowed by `other`.
+    pub fn includes(self, other: Permissions) -> bool {
+        (self.can_read() || !other.can_read()) && (self.can_write() || !other.can_write())
+    }
+
+    /// Returns `true` if these permissions allow the given operation.
+    pub fn allows(self, operation: Operation) -> bool {
+        match operation {
+            Operation::Read => self.can_read(),
+            Operation::Write => self.can_write(),
+        }
+    }
+
+    /// Returns the permissions allowing the given operations, if any.
+    fn from_access(read: bool, write: bool) -> Option<Permissions> {
+        match (read, write) {
+            (true, true) => Some(Permissions::ReadWrite),
+            (true, false) => Some(Permissions::ReadOnly),
+            (false, true) => Some(Permissions::WriteOnly),
+            (false, false) => None,
+        }
+    }
+
+    /// Returns the permissions allowing the operations allowed by both `self`
+    /// and `other`, if any.
+    fn intersection(self, other: Permissions) -> Option<Permissions> {
+        Permissions::from_access(
+            self.can_read() && other.can_read(),
+            self.can_write() && other.can_write(),
+        )
+    }
+
+    /// Returns the permissions allowing the operations allowed by either `self`
+    /// or `other`.
+    fn union(self, other: Permissions) -> Permissions {
+        Permissions::from_access(
+            self.can_read() || other.can_read(),
+            self.can_write() || other.can_write(),
+        )
+        .unwrap_or(self)
+    }
+}
 
 /// RAII structure used to release the shared read access of a lock when dropped.
-pub struct ProtectedReadGuard<'a, T>(RwLockReadGuard<'a, ProtectedBox<T>>);
+pub struct ProtectedReadGuard<'a, T, Id = u32> {
+    guard: ReadGuard<'a, ProtectedBox<T>>,
+    release: Release<'a>,
+    _marker: PhantomData<Id>,
+}
 
 /// RAII structure used to release the exclusive write access of a lock when dropped.
-pub struct ProtectedWriteGuard<'a, T>(RwLockWriteGuard<'a, ProtectedBox<T>>);
+pub struct ProtectedWriteGuard<'a, T, Id = u32> {
+    guard: WriteGuard<'a, ProtectedBox<T>>,
+    release: Release<'a>,
+    _marker: PhantomData<Id>,
+}
 
 /// A smart pointer that grants access to `T` for as long as the owner allows.
 ///
 /// The owner of `T` is allowed to create/remove users that have access to `T`.
-pub struct Protected<T, Access> {
-    inner: Arc<RwLock<ProtectedBox<T>>>,
-    access_key: Option<u32>,
+/// Users are identified by a [`UserId`], which is `u32` unless specified otherwise.
+pub struct Protected<T, Access, Id: UserId = u32> {
+    inner: Arc<Shared<T, Id>>,
+    capability: Option<Capability<Id>>,
+    /// Counts the handles and guards holding the access key of this user, or
+    /// `None` if this is an owner.
+    holders: Option<Arc<Holders>>,
+    counters: SharedCounters,
     _marker: PhantomData<Access>,
 }
 
-/// Inner type of `Protected<T>`.
-struct ProtectedBox<T> {
-    value: T,
-    access_keys: HashSet<u32>,
+/// Identifies the access key held by a user of `T`.
+///
+/// Every key is tagged with a generation that is never reused, so a handle whose
+/// key has been revoked stays revoked even if a new user is created with its ID.
+#[derive(Clone, PartialEq, Eq)]
+struct Capability<Id> {
+    id: Id,
+    generation: u64,
 }
 
-impl<T> Protected<T, Owner> {
-    /// Creates a `Protected` access to `T`.
+/// State shared by the owner and all the users of `T`.
+struct Shared<T, Id> {
+    lock: Lock<ProtectedBox<T>>,
+    /// Access keys of the users of `T`, which are locked apart from `T`, so that
+    /// the owners do not have to wait for guards to `T` to manage users.
     ///
-    /// The instance returned by this function is considered the _owner_ of `T`.
-    pub fn new(value: T) -> Protected<T, Owner> {
-        let inner = Arc::new(RwLock::new(ProtectedBox {
-            value,
-            access_keys: HashSet::new(),
-        }));
+    /// Guards check their access while holding the lock guarding `T`, so this
+    /// lock must never be held while waiting for that one.
+    control: RwLock<AccessControl<Id>>,
+    /// Bumped whenever a write guard to `T` is dropped.
+    version: AtomicU64,
+    /// Copy of `T` read by snapshots without acquiring `lock`, which is only
+    /// used if `T` is an `Arc`.
+    snapshot: Slot<T>,
+    released: ReleaseNotifier,
+    audit: Audit<Id>,
+    stats: Registry<Id>,
+}
 
-        Protected {
-            inner,
-            access_key: None,
+impl<T, Id> Shared<T, Id> {
+    /// Blocks until the lock guarding `T` is acquired with exclusive write access,
+    /// even if it has been poisoned.
+    fn write_ignoring_poison(&self) -> ProtectedWriteGuard<'_, T, Id> {
+        let guard = match self.lock.write(&self.released, None) {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(error)) => error.into_inner(),
+            Err(TryLockError::WouldBlock) => unreachable!("waiting forever never times out"),
+        };
+        ProtectedWriteGuard {
+            guard,
+            release: Release::exclusive(&self.released),
             _marker: PhantomData,
         }
     }
 
-    /// Grants access to `T` to a user with a given ID.
+    /// Locks the access keys of the users of `T` with shared read access.
     ///
-    /// This function returns a new `Protected` access to `T`, only if
-    /// a user with the given ID does not already exist.
-    pub fn create_user(&self, id: u32) -> Option<Protected<T, User>> {
-        let mut inner = self.inner.write().unwrap();
-        let access_keys = &mut inner.access_keys;
-        if access_keys.insert(id) {
-            Some(Protected {
-                inner: self.inner.clone(),
-                access_key: Some(id),
-                _marker: PhantomData,
-            })
-        } else {
-            None
-        }
+    /// This lock is never poisoned, as it is only held by this crate, which
+    /// leaves the access keys consistent even if observers panic.
+    fn control(&self) -> RwLockReadGuard<'_, AccessControl<Id>> {
+        self.control.read().unwrap_or_else(PoisonError::into_inner)
     }
 
-    /// Revokes access to `T` for a user with a given ID.
-    pub fn remove_user(&self, id: u32) {
-        let mut inner = self.inner.write().unwrap();
-        let access_keys = &mut inner.access_keys;
-        access_keys.remove(&id);
+    /// Locks the access keys of the users of `T` with exclusive write access,
+    /// like [`control`](Shared::control).
+    fn control_mut(&self) -> ControlGuard<'_, Id> {
+        ControlGuard {
+            guard: self.control.write().unwrap_or_else(PoisonError::into_inner),
+            _release: Release::exclusive(&self.released),
+        }
     }
+}
 
-    /// Locks this `T` so that the owner has shared read access to `T`.
+impl<T, Id: UserId> Shared<T, Id> {
+    /// Revokes every user, and destroys `T` once every guard to `T` has been
+    /// dropped, wiping it first if it is a secret.
     ///
-    /// # Panics
-    ///
-    /// Under the hood, `read` uses a [`std::sync::RwLock`], and this function panics
-    /// if the `RwLock` ever becomes poisoned.
-    pub fn read(&self) -> ProtectedReadGuard<T> {
-        ProtectedReadGuard(self.inner.read().unwrap())
+    /// Returns `false` if `T` had already been destroyed.
+    fn destroy(&self, mut control: ControlGuard<'_, Id>) -> bool {
+        if control.destroyed {
+            return false;
+        }
+        control.destroyed = true;
+        control.access_keys.clear();
+        self.stats.clear();
+        // No guard can be handed out anymore, and the guards that are still held
+        // are waited for without holding `control`, which they may need.
+        drop(control);
+        // `T` is dropped once the lock guarding it has been released.
+        let _destroyed = self.write_ignoring_poison().guard.destroy();
+        self.snapshot.clear();
+        true
     }
 
-    /// Locks this `T` so that the owner has exclusive write access to `T`.
-    ///
-    /// # Panics
-    ///
-    /// Under the hood, `write` uses a [`std::sync::RwLock`], and this function panics
-    /// if the `RwLock` ever becomes poisoned.
-    pub fn write(&self) -> ProtectedWriteGuard<T> {
-        ProtectedWriteGuard(self.inner.write().unwrap())
+    /// Removes the access key of a user with a given ID along with the keys of its
+    /// delegates, and returns `false` if there is no such user.
+    fn revoke(&self, control: &mut AccessControl<Id>, id: &Id) -> bool {
+        let revoked = control.revoke(id);
+        self.stats.remove(revoked.iter().map(|(id, _)| id));
+        let found = !revoked.is_empty();
+        for (id, _) in revoked {
+            self.audit.record(|| AuditEventKind::UserRemoved { id });
+        }
+        found
     }
-}
 
-impl<T> Protected<T, User> {
-    /// Locks this `T` so that this user has shared read access to `T`.
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the owner of `T` has been dropped,
-    /// or if the owner has revoked this user from accessing `T`.
-    ///
-    /// # Panics
-    ///
-    /// Under the hood, `read` uses a [`std::sync::RwLock`], and this function panics
-    /// if the `RwLock` ever becomes poisoned.
-    pub fn read(&self) -> Result<ProtectedReadGuard<T>, AccessDeniedError> {
-        if self.has_access() {
-            Ok(ProtectedReadGuard(self.inner.read().unwrap()))
-        } else {
-            Err(AccessDeniedError)
+    /// Gives up the access key matching a capability, once the last handle
+    /// holding it has been dropped.
+    fn release_key(&self, control: &mut AccessControl<Id>, capability: &Capability<Id>) {
+        // A revoked key has nothing left to give up, even if its ID has already
+        // been handed to a newer user.
+        if control.access_key(capability).is_none() {
+            return;
+        }
+        // The user resigns to its own access to `T`, and its sub-users lose the
+        // access it delegated to them.
+        let revoked = control.revoke(&capability.id);
+        self.stats.remove(revoked.iter().map(|(id, _)| id));
+        let mut revoked = revoked.into_iter();
+        revoked.next();
+        self.audit.record(|| AuditEventKind::UserDropped {
+            id: capability.id.clone(),
+        });
+        for (id, _) in revoked {
+            self.audit.record(|| AuditEventKind::UserRemoved { id });
         }
     }
+}
 
-    /// Locks this `T` so that this user has exclusive write access to `T`.
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the owner of `T` has been dropped,
-    /// or if the owner has revoked this user from accessing `T`.
-    ///
-    /// # Panics
-    ///
-    /// Under the hood, `write` uses a [`std::sync::RwLock`], and this function panics
-    /// if the `RwLock` ever becomes poisoned.
-    pub fn write(&self) -> Result<ProtectedWriteGuard<T>, AccessDeniedError> {
-        if self.has_access() {
-            Ok(ProtectedWriteGuard(self.inner.write().unwrap()))
-        } else {
-            Err(AccessDeniedError)
+/// RAII structure used to release the exclusive access to the access keys of
+/// the users of `T` when dropped.
+///
+/// Releasing it counts as an exclusive release, as the access of the users may
+/// have changed, so that users waiting for `T` to change notice it.
+struct ControlGuard<'a, Id> {
+    guard: RwLockWriteGuard<'a, AccessControl<Id>>,
+    _release: Release<'a>,
+}
+
+impl<Id> Deref for ControlGuard<'_, Id> {
+    type Target = AccessControl<Id>;
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<Id> DerefMut for ControlGuard<'_, Id> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+/// How long to wait for the lock guarding `T` to become available.
+#[derive(Clone, Copy)]
+enum Wait {
+    /// Block until the lock is available.
+    Forever,
+    /// Fail right away if the lock is not available.
+    Never,
+    /// Fail if the lock is not available by the given instant.
+    Until(Instant),
+}
+
+impl Wait {
+    /// Waits at most `timeout` from now.
+    fn timeout(timeout: Duration) -> Wait {
+        Instant::now()
+            .checked_add(timeout)
+            .map_or(Wait::Forever, Wait::Until)
+    }
+
+    /// Returns the instant at which waiting should stop, if any.
+    fn deadline(self) -> Option<Instant> {
+        match self {
+            Wait::Forever => None,
+            Wait::Never => Some(Instant::now()),
+            Wait::Until(deadline) => Some(deadline),
         }
     }
+}
+
+/// Value guarded by the lock of `Protected<T>`.
+struct ProtectedBox<T> {
+    /// The protected value, or `None` once it has been destroyed.
+    value: Option<T>,
+    /// Wipes a secret value before it is dropped, or `None` if `T` is no secret.
+    zeroize: Option<fn(&mut T)>,
+}
 
-    /// Checks if this instance of Protected has access to `T`.
+impl<T> ProtectedBox<T> {
+    /// Takes the value out so that it can be dropped, wiping it first if it is
+    /// a secret.
     ///
-    /// A user only has access to `T` if its access key is found in
-    /// the access keys for the `Protected<T>`.
-    fn has_access(&self) -> bool {
-        let inner = self.inner.read().unwrap();
-        let access_keys = &inner.access_keys;
-        access_keys.contains(&self.access_key.unwrap())
+    /// Secrets are wiped in place, as taking them out first would leave a copy
+    /// of any secret bytes stored inline behind.
+    fn destroy(&mut self) -> Option<T> {
+        if let (Some(value), Some(zeroize)) = (&mut self.value, self.zeroize) {
+            zeroize(value);
+        }
+        self.value.take()
     }
 }
 
-impl<T, A> Drop for Protected<T, A> {
+impl<T> Drop for ProtectedBox<T> {
     fn drop(&mut self) {
-        let mut inner = self.inner.write().unwrap();
-        let access_keys = &mut inner.access_keys;
-        if let Some(access_key) = self.access_key {
-            // If this is a user of `T`, the user should resign to its own access
-            // to T.
-            access_keys.remove(&access_key);
-        } else {
-            // If the access key is None, then this is the owner of `T` and
-            // all accesses to `T` should be revoked when the owner is dropped.
-            access_keys.clear();
-        }
+        self.destroy();
     }
 }
 
-impl<'a, T> Deref for ProtectedReadGuard<'a, T> {
-    type Target = T;
-    fn deref(&self) -> &Self::Target {
-        &self.0.value
+/// Access keys of the users of `T`, and everything else the owners of `T` manage.
+struct AccessControl<Id> {
+    access_keys: HashMap<Id, AccessKey<Id>>,
+    /// Permissions granted to each group of users, or `None` if the group
+    /// has been revoked.
+    groups: HashMap<String, Option<Permissions>>,
+    next_generation: u64,
+    /// Next ID to try in `create_anonymous_user`, or `None` once every `u32`
+    /// has been handed out. Unused with other types of IDs.
+    next_anonymous_id: Option<u32>,
+    clock: Box<dyn Clock>,
+    /// Number of owner handles that have not been dropped yet.
+    owners: usize,
+    owner_drop_policy: OwnerDropPolicy,
+    /// Whether the owner has suspended the access of every user until it is resumed.
+    users_suspended: bool,
+    /// Whether `T` has been destroyed, or is about to be once the guards to it
+    /// have been dropped.
+    destroyed: bool,
+}
+
+impl<Id: UserId> AccessControl<Id> {
+    /// Returns the access key matching a capability, unless it has been revoked.
+    fn access_key(&self, capability: &Capability<Id>) -> Option<&AccessKey<Id>> {
+        self.access_keys
+            .get(&capability.id)
+            .filter(|access_key| access_key.generation == capability.generation)
+    }
+
+    /// Like [`access_key`](AccessControl::access_key), but returns a mutable reference.
+    fn access_key_mut(&mut self, capability: &Capability<Id>) -> Option<&mut AccessKey<Id>> {
+        self.access_keys
+            .get_mut(&capability.id)
+            .filter(|access_key| access_key.generation == capability.generation)
+    }
+
+    /// Returns the IDs of a user and of every user it has delegated its access
+    /// to, directly or not, or nothing if there is no such user.
+    fn delegation_tree(&self, id: &Id) -> Vec<Id> {
+        let mut tree: Vec<Id> = self
+            .access_keys
+            .get_key_value(id)
+            .map(|(id, _)| id.clone())
+            .into_iter()
+            .collect();
+        let mut next = 0;
+        while let Some(id) = tree.get(next) {
+            let delegator = Capability {
+                id: id.clone(),
+                generation: self.access_keys[id].generation,
+            };
+            let delegates: Vec<Id> = self
+                .access_keys
+                .iter()
+                .filter(|(_, access_key)| access_key.parent.as_ref() == Some(&delegator))
+                .map(|(id, _)| id.clone())
+                .collect();
+            tree.extend(delegates);
+            next += 1;
+        }
+        tree
+    }
+
+    /// Removes the access key of a user along with the keys of every user it has
+    /// delegated its access to, directly or not, and returns the removed keys.
+    fn revoke(&mut self, id: &Id) -> Vec<(Id, AccessKey<Id>)> {
+        self.delegation_tree(id)
+            .into_iter()
+            .filter_map(|id| self.access_keys.remove_entry(&id))
+            .collect()
+    }
+
+    /// Returns the permissions actually granted by an access key, or `None` if
+    /// the groups it belongs to grant no permission.
+    ///
+    /// Keys that belong to no group grant their own permissions. Otherwise, they
+    /// grant the permissions of their groups, restricted to their own.
+    fn effective_permissions(&self, access_key: &AccessKey<Id>) -> Option<Permissions> {
+        if access_key.groups.is_empty() {
+            return Some(access_key.permissions);
+        }
+        access_key
+            .groups
+            .iter()
+            .filter_map(|group| self.groups.get(group).copied().flatten())
+            .reduce(Permissions::union)?
+            .intersection(access_key.permissions)
     }
 }
 
-impl<'a, T> Deref for ProtectedWriteGuard<'a, T> {
-    type Target = T;
-    fn deref(&self) -> &Self::Target {
-        &self.0.value
+/// Access granted by the owner to a single user.
+struct AccessKey<Id> {
+    generation: u64,
+    permissions: Permissions,
+    expires_at: Option<Instant>,
+    /// Key of the user that delegated this access, if it was not granted by the owner.
+    parent: Option<Capability<Id>>,
+    /// Names of the groups this key belongs to.
+    groups: HashSet<String>,
+    holders: Arc<Holders>,
+    /// Whether the owner has suspended this access until it is resumed.
+    suspended: bool,
+    /// Whether the owner is waiting for the guards obtained with this key, or
+    /// with the keys delegated from it, to be dropped before revoking it.
+    revoking: bool,
+}
+
+impl<Id> AccessKey<Id> {
+    /// Checks if the lease of this key has run out at the given instant.
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
     }
 }
 
-impl<'a, T> DerefMut for ProtectedWriteGuard<'a, T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0.value
+/// Counts the handles and the guards holding an access key.
+///
+/// These counters are shared by the key and its handles, so that handles can
+/// be cloned while a guard to `T` is held, and so that the owner can wait for
+/// the guards of a user without waiting for the guards of other users.
+struct Holders {
+    /// Number of handles holding the key, which gives up its access once the
+    /// last of them is dropped.
+    handles: AtomicUsize,
+    /// Number of guards obtained with the key that have not been dropped yet.
+    guards: AtomicUsize,
+}
+
+impl<T> Protected<T, Owner> {
+    /// Creates a `Protected` access to `T`.
+    ///
+    /// The instance returned by this function is considered the _owner_ of `T`.
+    pub fn new(value: T) -> Protected<T, Owner> {
+        Protected::with_ids(value)
+    }
+
+    /// Creates a `Protected` access to `T` that uses `clock` to decide
+    /// when the leases granted to its users expire.
+    ///
+    /// The instance returned by this function is considered the _owner_ of `T`.
+    pub fn with_clock(value: T, clock: impl Clock + 'static) -> Protected<T, Owner> {
+        Protected::with_ids_and_clock(value, clock)
+    }
+
+    /// Grants access to `T` to a new user whose ID is chosen by the owner.
+    ///
+    /// The user is only allowed to perform the operations included in
+    /// `permissions`. Returns the new `Protected` access to `T` along with
+    /// the ID of the user, which can be used to revoke it later.
+    ///
+    /// IDs are allocated in increasing order, skipping those of existing users,
+    /// and are never handed out twice by this function.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::IdsExhausted`] if every ID has
+    /// already been handed out, or an error if the lock guarding `T` has been
+    /// poisoned.
+    pub fn create_anonymous_user(
+        &self,
+        permissions: Permissions,
+    ) -> Result<(Protected<T, User>, u32), AccessError> {
+        let mut control = self.write_control()?;
+        let id = loop {
+            let id = control.next_anonymous_id.ok_or(AccessError::IdsExhausted)?;
+            control.next_anonymous_id = id.checked_add(1);
+            if !control.access_keys.contains_key(&id) {
+                break id;
+            }
+        };
+        let user = self.insert_key(&mut control, id, permissions, |_| None, None)?;
+        Ok((user, id))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl<T, Id: UserId> Protected<T, Owner, Id> {
+    /// Creates a `Protected` access to `T` whose users are identified by `Id`.
+    ///
+    /// The instance returned by this function is considered the _owner_ of `T`.
+    pub fn with_ids(value: T) -> Protected<T, Owner, Id> {
+        Protected::builder(value).user_ids().build()
+    }
 
-    #[test]
-    fn owner_can_read() {
-        let p = Protected::new(42);
-        let x = p.read();
-        assert_eq!(*x, 42);
+    /// Creates a `Protected` access to `T` whose users are identified by `Id`,
+    /// and that uses `clock` to decide when the leases granted to its users expire.
+    ///
+    /// The instance returned by this function is considered the _owner_ of `T`.
+    pub fn with_ids_and_clock(value: T, clock: impl Clock + 'static) -> Protected<T, Owner, Id> {
+        Protected::builder(value).user_ids().clock(clock).build()
     }
 
-    #[test]
-    fn owner_can_write() {
-        let p = Protected::new(42);
+    /// Creates another owner of `T`, which shares the management of `T` with
+    /// this one.
+    ///
+    /// Every owner may create and remove users. The [`OwnerDropPolicy`] only
+    /// applies once every owner has been dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn create_co_owner(&self) -> Result<Protected<T, Owner, Id>, AccessError<Id>> {
+        let mut control = self.write_control()?;
+        Ok(self.add_owner(&mut control))
+    }
 
-        {
-            let mut x = p.write();
-            *x = 43;
+    /// Hands the ownership of `T` over to a new owner, consuming this one
+    /// without revoking any user.
+    ///
+    /// This is equivalent to creating a co-owner and dropping this owner,
+    /// except that it also succeeds if the lock guarding `T` has been poisoned.
+    pub fn transfer_ownership(self) -> Protected<T, Owner, Id> {
+        let mut control = self.inner.control_mut();
+        self.add_owner(&mut control)
+    }
+
+    /// Counts a new owner of `T`, and returns a handle to it.
+    fn add_owner(&self, control: &mut AccessControl<Id>) -> Protected<T, Owner, Id> {
+        control.owners += 1;
+        self.inner.audit.record(|| AuditEventKind::CoOwnerCreated);
+        Protected {
+            inner: self.inner.clone(),
+            capability: None,
+            holders: None,
+            counters: self.counters.clone(),
+            _marker: PhantomData,
         }
+    }
 
-        let x = p.read();
-        assert_eq!(*x, 43);
+    /// Destroys `T` right away, revoking every user.
+    ///
+    /// Every guard to `T` must be dropped first, so this function blocks until then.
+    /// Afterwards, the owners and users of `T` are denied access with
+    /// [`AccessError::Destroyed`]. Secret values are wiped before being dropped.
+    ///
+    /// Unlike other functions, this function destroys `T` even if the lock guarding
+    /// `T` has been poisoned.
+    pub fn destroy(&self) {
+        if self.inner.destroy(self.inner.control_mut()) {
+            self.inner.audit.record(|| AuditEventKind::Destroyed);
+        }
     }
 
-    #[test]
-    fn owner_cannot_create_duplicated_users() {
-        let owner = Protected::new(42);
-        let user1 = owner.create_user(0);
-        let user2 = owner.create_user(0);
-        assert!(user1.is_some());
-        assert!(user2.is_none());
+    /// Returns the number of owners of `T` that have not been dropped yet.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn owner_count(&self) -> Result<usize, AccessError<Id>> {
+        Ok(self.read_control()?.owners)
     }
 
-    #[test]
-    fn owner_can_create_user_with_previously_dropped_id() {
-        let owner = Protected::new(42);
-        let user1 = owner.create_user(0);
-        assert!(user1.is_some());
-        drop(user1);
-        let user2 = owner.create_user(0);
-        assert!(user2.is_some());
+    /// Grants access to `T` to a user with a given ID.
+    ///
+    /// The user is only allowed to perform the operations included in
+    /// `permissions`.
+    ///
+    /// This function returns a new `Protected` access to `T`, only if
+    /// a user with the given ID does not already exist.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a user with the given ID already
+    /// exists, or if the lock guarding `T` has been poisoned.
+    pub fn create_user(
+        &self,
+        id: Id,
+        permissions: Permissions,
+    ) -> Result<Protected<T, User, Id>, AccessError<Id>> {
+        self.grant(id, permissions, |_| None)
     }
- 
-    #[test]
-    fn user_with_access_can_read() {
-        let owner = Protected::new(42);
-        let user = owner.create_user(0).unwrap();
-        let x = user.read().unwrap();
-        assert_eq!(*x, 42);
+
+    /// Grants access to `T` to a user with a given ID for a limited amount of time.
+    ///
+    /// Once `duration` has elapsed, the user is denied access to `T` as if the owner
+    /// had revoked it, unless the owner extends the lease with [`Protected::renew`].
+    ///
+    /// This function returns a new `Protected` access to `T`, only if
+    /// a user with the given ID does not already exist.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a user with the given ID already
+    /// exists, or if the lock guarding `T` has been poisoned.
+    pub fn create_user_for(
+        &self,
+        id: Id,
+        permissions: Permissions,
+        duration: Duration,
+    ) -> Result<Protected<T, User, Id>, AccessError<Id>> {
+        self.grant(id, permissions, |now| now.checked_add(duration))
     }
 
-    #[test]
-    fn user_with_revoked_access_cannot_read() {
-        let owner = Protected::new(42);
-        let user = owner.create_user(0).unwrap();
-        owner.remove_user(0);
-        assert!(user.read().is_err())
+    /// Grants access to `T` to a user with a given ID until a given deadline.
+    ///
+    /// Once `deadline` is reached, the user is denied access to `T` as if the owner
+    /// had revoked it, unless the owner extends the lease with [`Protected::renew`].
+    ///
+    /// This function returns a new `Protected` access to `T`, only if
+    /// a user with the given ID does not already exist.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a user with the given ID already
+    /// exists, or if the lock guarding `T` has been poisoned.
+    pub fn create_user_until(
+        &self,
+        id: Id,
+        permissions: Permissions,
+        deadline: Instant,
+    ) -> Result<Protected<T, User, Id>, AccessError<Id>> {
+        self.grant(id, permissions, |_| Some(deadline))
     }
 
-    #[test]
-    fn user_without_access_cannot_read() {
-        let owner = Protected::new(42);
-        let user = owner.create_user(0).unwrap();
-        drop(owner);
-        assert!(user.read().is_err())
+    /// Grants read-only access to `T` to a user with a given ID.
+    ///
+    /// Unlike [`Protected::create_user`], the returned user cannot even attempt
+    /// to write `T`, since `Protected<T, ReadOnlyUser>` has no `write` method.
+    ///
+    /// This function returns a new `Protected` access to `T`, only if
+    /// a user with the given ID does not already exist.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a user with the given ID already
+    /// exists, or if the lock guarding `T` has been poisoned.
+    pub fn create_read_only_user(
+        &self,
+        id: Id,
+    ) -> Result<Protected<T, ReadOnlyUser, Id>, AccessError<Id>> {
+        self.grant(id, Permissions::ReadOnly, |_| None)
     }
 
-    #[test]
-    fn user_with_access_can_write() {
-        let owner = Protected::new(42);
-        let user = owner.create_user(0).unwrap();
-        {
-            let mut x = user.write().unwrap();
-            *x = 43;
+    /// Extends the lease of the user with a given ID so that it expires
+    /// `duration` from now.
+    ///
+    /// Returns `false` if there is no such user, or if its lease has already expired.
+    /// Renewing a user that was created without a time limit puts it on a lease.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn renew(&self, id: Id, duration: Duration) -> Result<bool, AccessError<Id>> {
+        let mut control = self.write_control()?;
+        let now = control.clock.now();
+        match control.access_keys.get_mut(&id) {
+            Some(access_key) if !access_key.is_expired(now) => {
+                access_key.expires_at = now.checked_add(duration);
+                Ok(true)
+            }
+            _ => Ok(false),
         }
-        let x = user.read().unwrap();
-        assert_eq!(*x, 43);
     }
 
-    #[test]
-    fn user_with_revoked_access_cannot_write() {
-        let owner = Protected::new(42);
-        let user = owner.create_user(0).unwrap();
-        owner.remove_user(0);
-        assert!(user.write().is_err())
+    /// Inserts an access key with the given permissions and returns a user
+    /// holding that key.
+    ///
+    /// `expires_at` computes the deadline of the key from the current instant.
+    fn grant<A: UserAccess>(
+        &self,
+        id: Id,
+        permissions: Permissions,
+        expires_at: impl FnOnce(Instant) -> Option<Instant>,
+    ) -> Result<Protected<T, A, Id>, AccessError<Id>> {
+        let mut control = self.write_control()?;
+        self.insert_key(&mut control, id, permissions, expires_at, None)
     }
 
-    #[test]
-    fn user_without_access_cannot_write() {
-        let owner = Protected::new(42);
-        let user = owner.create_user(0).unwrap();
-        drop(owner);
-        assert!(user.write().is_err())
+    /// Revokes access to `T` for a user with a given ID, along with every user
+    /// that it has delegated its access to, directly or not.
+    ///
+    /// Once this function returns, the user cannot obtain any new guard to `T`.
+    /// Since revoking requires exclusive access to the lock guarding `T`, this
+    /// function blocks until every guard that is currently held has been dropped,
+    /// including the guards of other users. See [`Protected::revoke_and_wait`] to
+    /// only wait for the guards of the revoked users.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn remove_user(&self, id: Id) -> Result<(), AccessError<Id>> {
+        let _guard = self.lock_write(Wait::Forever, Stopwatch::start())?;
+        self.inner.revoke(&mut self.inner.control_mut(), &id);
+        Ok(())
     }
 
-    #[test]
-    fn user_can_read_something_written_by_another_user() {
-        let owner = Protected::new(42);
-        let user1 = owner.create_user(0).unwrap();
-        let user2 = owner.create_user(1).unwrap();
-        {
-            let mut x = user1.write().unwrap();
-            *x = 43;
+    /// Revokes access to `T` for a user with a given ID and its delegates, like
+    /// [`Protected::remove_user`], once every guard obtained by those users has
+    /// been dropped.
+    ///
+    /// Guards obtained by other users are not waited for. While waiting, the
+    /// users cannot obtain any new guard to `T`. Once this function returns,
+    /// nothing obtained by the revoked users can touch `T` anymore. Returns
+    /// `false` if there is no such user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn revoke_and_wait(&self, id: Id) -> Result<bool, AccessError<Id>> {
+        self.revoke_and_wait_until(id, Wait::Forever)
+    }
+
+    /// Revokes access to `T` for a user with a given ID and its delegates, like
+    /// [`Protected::revoke_and_wait`], blocking for at most `timeout` until every
+    /// guard obtained by those users has been dropped.
+    ///
+    /// Returns `false` if there is no such user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::TimedOut`] if the guards of the users
+    /// are still held after `timeout`. A timeout can only happen before revoking, so
+    /// the users then keep their access, as well as their guards. This function will
+    /// also return an error if the lock guarding `T` has been poisoned.
+    pub fn revoke_and_wait_timeout(
+        &self,
+        id: Id,
+        timeout: Duration,
+    ) -> Result<bool, AccessError<Id>> {
+        self.revoke_and_wait_until(id, Wait::timeout(timeout))
+    }
+
+    /// Returns the number of guards obtained by the user with a given ID that
+    /// have not been dropped yet.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn guards_held_by(&self, id: Id) -> Result<usize, AccessError<Id>> {
+        let control = self.read_control()?;
+        Ok(control.access_keys.get(&id).map_or(0, |access_key| {
+            access_key.holders.guards.load(Ordering::SeqCst)
+        }))
+    }
+
+    /// Returns the number of live handles to the user with a given ID, including
+    /// clones and weak handles, or 0 if there is no such user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn handle_count(&self, id: Id) -> Result<usize, AccessError<Id>> {
+        let control = self.read_control()?;
+        Ok(control.access_keys.get(&id).map_or(0, |access_key| {
+            access_key.holders.handles.load(Ordering::SeqCst)
+        }))
+    }
+
+    /// Stops a user and its delegates from obtaining new guards, waits as long as
+    /// `wait` allows for the guards they hold to be dropped, then removes their
+    /// access keys.
+    ///
+    /// If waiting times out, the user is let back in without having been revoked.
+    fn revoke_and_wait_until(&self, id: Id, wait: Wait) -> Result<bool, AccessError<Id>> {
+        let (capability, holders) = {
+            let mut control = self.write_control()?;
+            let Some(access_key) = control.access_keys.get_mut(&id) else {
+                return Ok(false);
+            };
+            access_key.revoking = true;
+            let capability = Capability {
+                id: id.clone(),
+                generation: access_key.generation,
+            };
+            let holders: Vec<_> = control
+                .delegation_tree(&id)
+                .iter()
+                .map(|id| control.access_keys[id].holders.clone())
+                .collect();
+            (capability, holders)
+        };
+
+        // Guards are counted while checking access, under the lock of the access
+        // keys, so no guard is handed out to these users past this point.
+        let dropped = self.inner.released.wait_until(wait.deadline(), || {
+            holders
+                .iter()
+                .all(|holders| holders.guards.load(Ordering::SeqCst) == 0)
+                .then_some(())
+        });
+        let mut control = self.inner.control_mut();
+        if dropped.is_none() {
+            if let Some(access_key) = control.access_key_mut(&capability) {
+                access_key.revoking = false;
+            }
+            return Err(AccessError::TimedOut { id: None });
+        }
+        // The user may have been dropped or revoked by someone else meanwhile.
+        if control.access_key(&capability).is_some() {
+            self.inner.revoke(&mut control, &id);
+        }
+        Ok(true)
+    }
+
+    /// Returns the ID of the user that delegated its access to the user with
+    /// a given ID, or `None` if there is no such user or it was created by the owner.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn parent_of(&self, id: Id) -> Result<Option<Id>, AccessError<Id>> {
+        let control = self.read_control()?;
+        Ok(control
+            .access_keys
+            .get(&id)
+            .and_then(|access_key| access_key.parent.as_ref())
+            .map(|parent| parent.id.clone()))
+    }
+
+    /// Returns the IDs of the users to which the user with a given ID has
+    /// delegated its access directly, in no particular order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn sub_users_of(&self, id: Id) -> Result<Vec<Id>, AccessError<Id>> {
+        let control = self.read_control()?;
+        let Some(access_key) = control.access_keys.get(&id) else {
+            return Ok(Vec::new());
+        };
+        let delegator = Capability {
+            id,
+            generation: access_key.generation,
+        };
+        Ok(control
+            .access_keys
+            .iter()
+            .filter(|(_, access_key)| access_key.parent.as_ref() == Some(&delegator))
+            .map(|(id, _)| id.clone())
+            .collect())
+    }
+
+    /// Locks this `T` so that the owner has shared read access to `T`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned,
+    /// which happens when a thread panics while holding a write guard to `T`.
+    pub fn read(&self) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.read_checked(Wait::Forever)
+    }
+
+    /// Attempts to lock this `T` so that the owner has shared read access to `T`,
+    /// without blocking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::WouldBlock`] if `T` is currently
+    /// locked for writing, or an error if the lock guarding `T` has been poisoned.
+    pub fn try_read(&self) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.read_checked(Wait::Never)
+    }
+
+    /// Locks this `T` so that the owner has shared read access to `T`,
+    /// blocking for at most `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::TimedOut`] if `T` is still locked
+    /// for writing after `timeout`, or an error if the lock guarding `T` has been
+    /// poisoned.
+    pub fn read_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.read_checked(Wait::timeout(timeout))
+    }
+
+    /// Locks this `T` so that the owner has exclusive write access to `T`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned,
+    /// which happens when a thread panics while holding a write guard to `T`.
+    pub fn write(&self) -> Result<ProtectedWriteGuard<'_, T, Id>, AccessError<Id>> {
+        self.write_checked(Wait::Forever)
+    }
+
+    /// Attempts to lock this `T` so that the owner has exclusive write access to `T`,
+    /// without blocking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::WouldBlock`] if `T` is currently
+    /// locked, or an error if the lock guarding `T` has been poisoned.
+    pub fn try_write(&self) -> Result<ProtectedWriteGuard<'_, T, Id>, AccessError<Id>> {
+        self.write_checked(Wait::Never)
+    }
+
+    /// Locks this `T` so that the owner has exclusive write access to `T`,
+    /// blocking for at most `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::TimedOut`] if `T` is still locked
+    /// after `timeout`, or an error if the lock guarding `T` has been poisoned.
+    pub fn write_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<ProtectedWriteGuard<'_, T, Id>, AccessError<Id>> {
+        self.write_checked(Wait::timeout(timeout))
+    }
+}
+
+impl<T, A: UserAccess, Id: UserId> Protected<T, A, Id> {
+    /// Shares the access of this user with a new sub-user with a given ID.
+    ///
+    /// The sub-user is only allowed to perform the operations included in
+    /// `permissions`, which must be included in the permissions currently granted
+    /// to this user.
+    /// It loses its access to `T` whenever this user does: when this user is
+    /// revoked or dropped, and for as long as the lease of this user has expired.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::PermissionsExceeded`] if `permissions`
+    /// exceed those of this user, an error if a user with the given ID already
+    /// exists, or any of the errors returned by [`read`](Protected::read) if this
+    /// user is denied access to `T`.
+    pub fn delegate(
+        &self,
+        id: Id,
+        permissions: Permissions,
+    ) -> Result<Protected<T, A, Id>, AccessError<Id>> {
+        let mut control = self.write_control()?;
+        let Some(parent) = &self.capability else {
+            unreachable!("users always hold a capability");
+        };
+        let parent_permissions = self
+            .check_key(&control)?
+            .map_or(Permissions::ReadWrite, |(_, permissions)| permissions);
+        if !parent_permissions.includes(permissions) {
+            return Err(AccessError::PermissionsExceeded {
+                id: parent.id.clone(),
+                permissions: parent_permissions,
+                requested: permissions,
+            });
+        }
+        self.insert_key(
+            &mut control,
+            id,
+            permissions,
+            |_| None,
+            Some(parent.clone()),
+        )
+    }
+
+    /// Locks this `T` so that this user has shared read access to `T`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the owner of `T` has been dropped,
+    /// if the owner has revoked this user from accessing `T`, if the lease of this
+    /// user has expired, if this user is not allowed to read `T`, or if the
+    /// lock guarding `T` has been poisoned.
+    pub fn read(&self) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.read_checked(Wait::Forever)
+    }
+
+    /// Attempts to lock this `T` so that this user has shared read access to `T`,
+    /// without blocking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::WouldBlock`] if `T` is currently
+    /// locked for writing, or any of the errors returned by
+    /// [`read`](Protected::read) if this user is denied access to `T`.
+    pub fn try_read(&self) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.read_checked(Wait::Never)
+    }
+
+    /// Locks this `T` so that this user has shared read access to `T`,
+    /// blocking for at most `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::TimedOut`] if `T` is still locked
+    /// for writing after `timeout`, or any of the errors returned by
+    /// [`read`](Protected::read) if this user is denied access to `T`.
+    pub fn read_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.read_checked(Wait::timeout(timeout))
+    }
+}
+
+impl<T, Id: UserId> Protected<T, User, Id> {
+    /// Locks this `T` so that this user has exclusive write access to `T`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the owner of `T` has been dropped,
+    /// if the owner has revoked this user from accessing `T`, if the lease of this
+    /// user has expired, if this user is not allowed to write `T`, or if the
+    /// lock guarding `T` has been poisoned.
+    pub fn write(&self) -> Result<ProtectedWriteGuard<'_, T, Id>, AccessError<Id>> {
+        self.write_checked(Wait::Forever)
+    }
+
+    /// Attempts to lock this `T` so that this user has exclusive write access to `T`,
+    /// without blocking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::WouldBlock`] if `T` is currently
+    /// locked, or any of the errors returned by [`write`](Protected::write) if this
+    /// user is denied access to `T`.
+    pub fn try_write(&self) -> Result<ProtectedWriteGuard<'_, T, Id>, AccessError<Id>> {
+        self.write_checked(Wait::Never)
+    }
+
+    /// Locks this `T` so that this user has exclusive write access to `T`,
+    /// blocking for at most `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::TimedOut`] if `T` is still locked
+    /// after `timeout`, or any of the errors returned by [`write`](Protected::write)
+    /// if this user is denied access to `T`.
+    pub fn write_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<ProtectedWriteGuard<'_, T, Id>, AccessError<Id>> {
+        self.write_checked(Wait::timeout(timeout))
+    }
+}
+
+impl<T, A, Id: UserId> Protected<T, A, Id> {
+    /// Returns the ID of this user, or `None` if this is the owner of `T`.
+    fn id(&self) -> Option<Id> {
+        self.capability
+            .as_ref()
+            .map(|capability| capability.id.clone())
+    }
+
+    /// Checks the access keys of the users of `T` to find out if this instance of
+    /// Protected may perform the given operation.
+    ///
+    /// The owner always has access to `T`. A user only has access to `T` if it
+    /// holds a valid key, as checked by [`check_key`](Protected::check_key), and
+    /// the permissions granted by the key allow the operation.
+    fn check_access(
+        &self,
+        control: &AccessControl<Id>,
+        operation: Operation,
+    ) -> Result<(), AccessError<Id>> {
+        match self.check_key(control)? {
+            Some((_, permissions)) if !permissions.allows(operation) => {
+                Err(AccessError::PermissionDenied {
+                    id: self.id().expect("only users hold access keys"),
+                    permissions,
+                    operation,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that this instance of Protected still holds a valid access key,
+    /// and returns that key along with the permissions it grants if this is a user.
+    ///
+    /// A key is valid if it is found in the access keys for the `Protected<T>`,
+    /// neither its lease nor the lease of any of the keys it was delegated from
+    /// has expired, none of them is being revoked, and the groups of all these
+    /// keys grant some permission. The key grants the permissions granted by all
+    /// these keys.
+    fn check_key<'b>(
+        &self,
+        control: &'b AccessControl<Id>,
+    ) -> Result<Option<(&'b AccessKey<Id>, Permissions)>, AccessError<Id>> {
+        if control.destroyed {
+            return Err(AccessError::Destroyed { id: self.id() });
+        }
+        let Some(capability) = &self.capability else {
+            return Ok(None);
+        };
+        let id = || capability.id.clone();
+        let Some(access_key) = control.access_key(capability) else {
+            return Err(if control.owners == 0 {
+                AccessError::OwnerDropped { id: id() }
+            } else {
+                AccessError::Revoked { id: id() }
+            });
+        };
+        if control.users_suspended {
+            return Err(AccessError::Suspended { id: id() });
+        }
+
+        let now = control.clock.now();
+        let mut delegator = access_key;
+        let mut permissions = Permissions::ReadWrite;
+        loop {
+            if delegator.revoking {
+                return Err(AccessError::Revoked { id: id() });
+            }
+            if delegator.is_expired(now) {
+                return Err(AccessError::Expired { id: id() });
+            }
+            if delegator.suspended {
+                return Err(AccessError::Suspended { id: id() });
+            }
+            permissions = control
+                .effective_permissions(delegator)
+                .and_then(|granted| granted.intersection(permissions))
+                .ok_or_else(|| AccessError::GroupsDenied { id: id() })?;
+            match &delegator.parent {
+                Some(parent) => {
+                    delegator = control
+                        .access_key(parent)
+                        .ok_or_else(|| AccessError::Revoked { id: id() })?;
+                }
+                None => return Ok(Some((access_key, permissions))),
+            }
+        }
+    }
+
+    /// Inserts an access key for the users of `T`, and returns a user holding that key.
+    ///
+    /// `expires_at` computes the deadline of the key from the current instant.
+    /// A key whose lease has expired does not prevent a new key with the same ID
+    /// from being inserted, in which case the old key is revoked along with its
+    /// delegates. Each key gets a new generation, so that users holding a previous
+    /// key with the same ID do not regain access to `T`.
+    fn insert_key<B: UserAccess>(
+        &self,
+        control: &mut AccessControl<Id>,
+        id: Id,
+        permissions: Permissions,
+        expires_at: impl FnOnce(Instant) -> Option<Instant>,
+        parent: Option<Capability<Id>>,
+    ) -> Result<Protected<T, B, Id>, AccessError<Id>> {
+        let now = control.clock.now();
+        if control
+            .access_keys
+            .get(&id)
+            .is_some_and(|access_key| !access_key.is_expired(now))
+        {
+            return Err(AccessError::UserExists { id });
+        }
+        let revoked = control.revoke(&id);
+        self.inner.stats.remove(revoked.iter().map(|(id, _)| id));
+
+        let generation = control.next_generation;
+        control.next_generation += 1;
+        let holders = Arc::new(Holders {
+            handles: AtomicUsize::new(1),
+            guards: AtomicUsize::new(0),
+        });
+        self.inner.audit.record(|| match &parent {
+            Some(parent) => AuditEventKind::UserDelegated {
+                id: id.clone(),
+                parent: parent.id.clone(),
+                permissions,
+            },
+            None => AuditEventKind::UserCreated {
+                id: id.clone(),
+                permissions,
+            },
+        });
+        control.access_keys.insert(
+            id.clone(),
+            AccessKey {
+                generation,
+                permissions,
+                expires_at: expires_at(now),
+                parent,
+                groups: HashSet::new(),
+                holders: holders.clone(),
+                suspended: false,
+                revoking: false,
+            },
+        );
+        Ok(Protected {
+            inner: self.inner.clone(),
+            counters: self.inner.stats.user(&id),
+            capability: Some(Capability { id, generation }),
+            holders: Some(holders),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Acquires the inner lock with shared read access, waiting as long as `wait`
+    /// allows, and checks that this instance of Protected may read `T`.
+    ///
+    /// Access is checked while holding the very lock that backs the returned guard,
+    /// so `T` cannot be destroyed in between.
+    fn read_checked(&self, wait: Wait) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        let waiting = Stopwatch::start();
+        let mut guard = self.lock_read(wait, waiting)?;
+        self.authorize(&mut guard.release, Operation::Read, waiting)?;
+        guard.release.measure(&self.counters);
+        Ok(guard)
+    }
+
+    /// Acquires the inner lock with exclusive write access, waiting as long as `wait`
+    /// allows, and checks that this instance of Protected may write `T`.
+    ///
+    /// Access is checked while holding the very lock that backs the returned guard,
+    /// so `T` cannot be destroyed in between.
+    fn write_checked(&self, wait: Wait) -> Result<ProtectedWriteGuard<'_, T, Id>, AccessError<Id>> {
+        let waiting = Stopwatch::start();
+        let mut guard = self.lock_write(wait, waiting)?;
+        self.authorize(&mut guard.release, Operation::Write, waiting)?;
+        guard.release.measure(&self.counters);
+        guard.release.bump(&self.inner.version);
+        Ok(guard)
+    }
+
+    /// Checks access like [`check_access`](Protected::check_access), and reports
+    /// the outcome to the audit observers and to the statistics of this instance,
+    /// which has been waiting for the lock since `waiting` was started.
+    ///
+    /// If access is granted to a user, `release` counts the guard holding it
+    /// among the guards held by the user. It starts counting before the access
+    /// keys are unlocked, so that the owner cannot miss that guard while waiting
+    /// for the guards of the user to be dropped.
+    fn authorize<'a>(
+        &'a self,
+        release: &mut Release<'a>,
+        operation: Operation,
+        waiting: Stopwatch,
+    ) -> Result<(), AccessError<Id>> {
+        let result = {
+            let control = self.inner.control();
+            let result = self.check_access(&control, operation);
+            if let (Ok(()), Some(holders)) = (&result, &self.holders) {
+                release.track(&holders.guards);
+            }
+            result
+        };
+        self.report(operation, waiting, &result);
+        result
+    }
+
+    /// Reports the outcome of checking access to the audit observers and to
+    /// the statistics of this instance, which has been waiting for `T` since
+    /// `waiting` was started.
+    fn report(
+        &self,
+        operation: Operation,
+        waiting: Stopwatch,
+        result: &Result<(), AccessError<Id>>,
+    ) {
+        match result {
+            Ok(_) => self.counters.granted(operation, waiting),
+            Err(_) => self.counters.denied(waiting),
+        }
+        self.inner
+            .audit
+            .record(|| match (result, &self.capability) {
+                (Err(error), Some(capability)) => AuditEventKind::Denied {
+                    id: capability.id.clone(),
+                    operation,
+                    error: error.clone(),
+                },
+                _ => AuditEventKind::Granted {
+                    id: self.id(),
+                    operation,
+                },
+            });
+    }
+
+    /// Reports the poisoning of the lock guarding `T` as an [`AccessError`].
+    fn check_poison(&self) -> Result<(), AccessError<Id>> {
+        if self.inner.lock.is_poisoned() {
+            return Err(AccessError::Poisoned { id: self.id() });
+        }
+        Ok(())
+    }
+
+    /// Locks the access keys of the users of `T` with shared read access,
+    /// reporting the poisoning of the lock guarding `T` as an [`AccessError`].
+    fn read_control(&self) -> Result<RwLockReadGuard<'_, AccessControl<Id>>, AccessError<Id>> {
+        self.check_poison()?;
+        Ok(self.inner.control())
+    }
+
+    /// Locks the access keys of the users of `T` with exclusive write access,
+    /// reporting the poisoning of the lock guarding `T` as an [`AccessError`].
+    fn write_control(&self) -> Result<ControlGuard<'_, Id>, AccessError<Id>> {
+        self.check_poison()?;
+        Ok(self.inner.control_mut())
+    }
+
+    /// Acquires the inner lock with shared read access, waiting as long as
+    /// `wait` allows.
+    fn lock_read(
+        &self,
+        wait: Wait,
+        waiting: Stopwatch,
+    ) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        let guard = self.lock_with(wait, waiting, |deadline| {
+            self.inner.lock.read(&self.inner.released, deadline)
+        })?;
+        Ok(ProtectedReadGuard {
+            guard,
+            release: Release::new(&self.inner.released),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Acquires the inner lock with exclusive write access, waiting as long as
+    /// `wait` allows.
+    fn lock_write(
+        &self,
+        wait: Wait,
+        waiting: Stopwatch,
+    ) -> Result<ProtectedWriteGuard<'_, T, Id>, AccessError<Id>> {
+        let guard = self.lock_with(wait, waiting, |deadline| {
+            self.inner.lock.write(&self.inner.released, deadline)
+        })?;
+        Ok(ProtectedWriteGuard {
+            guard,
+            release: Release::exclusive(&self.inner.released),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Acquires the inner lock using `lock`, which waits until the deadline set
+    /// by `wait`, and maps the ways in which it can fail to an [`AccessError`].
+    ///
+    /// Failures are reported to the statistics of this instance, which has been
+    /// waiting for the lock since `waiting` was started.
+    fn lock_with<G>(
+        &self,
+        wait: Wait,
+        waiting: Stopwatch,
+        lock: impl FnOnce(Option<Instant>) -> TryLockResult<G>,
+    ) -> Result<G, AccessError<Id>> {
+        lock(wait.deadline()).map_err(|error| match error {
+            TryLockError::Poisoned(error) => {
+                // Waiters are notified once the lock has been released, as
+                // `Release` would have done.
+                drop(error);
+                self.inner.released.notify();
+                self.counters.waited(waiting);
+                AccessError::Poisoned { id: self.id() }
+            }
+            TryLockError::WouldBlock => {
+                self.counters.timed_out(waiting);
+                match wait {
+                    Wait::Never => AccessError::WouldBlock { id: self.id() },
+                    Wait::Forever | Wait::Until(_) => AccessError::TimedOut { id: self.id() },
+                }
+            }
+        })
+    }
+}
+
+impl<T, A: UserAccess, Id: UserId> Clone for Protected<T, A, Id> {
+    /// Returns another handle to this user, which shares its access key.
+    ///
+    /// The key is only given up once every handle sharing it has been dropped,
+    /// unless the owner revokes it first.
+    fn clone(&self) -> Protected<T, A, Id> {
+        let (Some(capability), Some(holders)) = (&self.capability, &self.holders) else {
+            unreachable!("users always hold a capability");
+        };
+        holders.handles.fetch_add(1, Ordering::SeqCst);
+        Protected {
+            inner: self.inner.clone(),
+            capability: Some(capability.clone()),
+            holders: Some(holders.clone()),
+            counters: self.counters.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, A, Id: UserId> Drop for Protected<T, A, Id> {
+    fn drop(&mut self) {
+        // Only the last handle holding an access key needs to lock the access
+        // keys, to give the key up.
+        if let Some(holders) = &self.holders {
+            if holders.handles.fetch_sub(1, Ordering::SeqCst) > 1 {
+                return;
+            }
+        }
+        // Access keys must be released even if another thread panicked while
+        // holding the lock guarding `T`, so poisoning is deliberately ignored here.
+        let mut control = self.inner.control_mut();
+        if let Some(capability) = &self.capability {
+            self.inner.release_key(&mut control, capability);
+            return;
+        }
+        // If the capability is None, then this is an owner of `T`, and the
+        // owner drop policy applies when the last owner is dropped.
+        control.owners -= 1;
+        if control.owners == 0 {
+            match control.owner_drop_policy {
+                OwnerDropPolicy::RevokeUsers => {
+                    control.access_keys.clear();
+                    self.inner.stats.clear();
+                }
+                OwnerDropPolicy::DestroyValue => {
+                    self.inner.destroy(control);
+                }
+                OwnerDropPolicy::KeepUsers => {}
+            }
+            self.inner.audit.record(|| AuditEventKind::OwnerDropped);
+        } else {
+            self.inner.audit.record(|| AuditEventKind::CoOwnerDropped);
+        }
+    }
+}
+
+// Guards are only handed out after checking that `T` has not been destroyed,
+// which cannot happen while they are held.
+const DESTROYED: &str = "guards cannot outlive the value they guard";
+
+impl<T, A, Id: UserId + fmt::Debug> fmt::Debug for Protected<T, A, Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The value is never shown, as this handle might not be allowed to read it.
+        f.debug_struct("Protected")
+            .field(
+                "id",
+                &self.capability.as_ref().map(|capability| &capability.id),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: fmt::Debug, Id> fmt::Debug for ProtectedReadGuard<'_, T, Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.guard.fmt_value(f)
+    }
+}
+
+impl<T: fmt::Debug, Id> fmt::Debug for ProtectedWriteGuard<'_, T, Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.guard.fmt_value(f)
+    }
+}
+
+impl<T: fmt::Debug> ProtectedBox<T> {
+    /// Formats the value, unless it is a secret.
+    fn fmt_value(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(_) if self.zeroize.is_some() => f.write_str("<redacted>"),
+            Some(value) => value.fmt(f),
+            None => f.write_str("<destroyed>"),
+        }
+    }
+}
+
+impl<'a, T, Id> Deref for ProtectedReadGuard<'a, T, Id> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.guard.value.as_ref().expect(DESTROYED)
+    }
+}
+
+impl<'a, T, Id> Deref for ProtectedWriteGuard<'a, T, Id> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.guard.value.as_ref().expect(DESTROYED)
+    }
+}
+
+impl<'a, T, Id> DerefMut for ProtectedWriteGuard<'a, T, Id> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard.value.as_mut().expect(DESTROYED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+
+    #[test]
+    fn owner_can_read() {
+        let p = Protected::new(42);
+        let x = p.read().unwrap();
+        assert_eq!(*x, 42);
+    }
+
+    #[test]
+    fn owner_can_write() {
+        let p = Protected::new(42);
+
+        {
+            let mut x = p.write().unwrap();
+            *x = 43;
+        }
+
+        let x = p.read().unwrap();
+        assert_eq!(*x, 43);
+    }
+
+    #[test]
+    fn owner_cannot_create_duplicated_users() {
+        let owner = Protected::new(42);
+        let user1 = owner.create_user(0, Permissions::ReadWrite);
+        let user2 = owner.create_user(0, Permissions::ReadWrite);
+        assert!(user1.is_ok());
+        assert_eq!(user2.err(), Some(AccessError::UserExists { id: 0 }));
+    }
+
+    #[test]
+    fn owner_can_create_user_with_previously_dropped_id() {
+        let owner = Protected::new(42);
+        let user1 = owner.create_user(0, Permissions::ReadWrite);
+        assert!(user1.is_ok());
+        drop(user1);
+        let user2 = owner.create_user(0, Permissions::ReadWrite);
+        assert!(user2.is_ok());
+    }
+
+    #[test]
+    fn user_with_access_can_read() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let x = user.read().unwrap();
+        assert_eq!(*x, 42);
+    }
+
+    #[test]
+    fn user_with_revoked_access_cannot_read() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        owner.remove_user(0).unwrap();
+        assert!(user.read().is_err())
+    }
+
+    #[test]
+    fn user_without_access_cannot_read() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        drop(owner);
+        assert!(user.read().is_err())
+    }
+
+    #[test]
+    fn user_with_access_can_write() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        {
+            let mut x = user.write().unwrap();
+            *x = 43;
+        }
+        let x = user.read().unwrap();
+        assert_eq!(*x, 43);
+    }
+
+    #[test]
+    fn user_with_revoked_access_cannot_write() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        owner.remove_user(0).unwrap();
+        assert!(user.write().is_err())
+    }
+
+    #[test]
+    fn user_without_access_cannot_write() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        drop(owner);
+        assert!(user.write().is_err())
+    }
+
+    #[test]
+    fn user_can_read_something_written_by_another_user() {
+        let owner = Protected::new(42);
+        let user1 = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let user2 = owner.create_user(1, Permissions::ReadWrite).unwrap();
+        {
+            let mut x = user1.write().unwrap();
+            *x = 43;
+        }
+        let x = user2.read().unwrap();
+        assert_eq!(*x, 43);
+    }
+
+    #[test]
+    fn read_only_user_can_read() {
+        let owner = Protected::new(42);
+        let user = owner.create_read_only_user(0).unwrap();
+        let x = user.read().unwrap();
+        assert_eq!(*x, 42);
+    }
+
+    #[test]
+    fn read_only_user_cannot_write() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadOnly).unwrap();
+        assert_eq!(
+            user.write().err(),
+            Some(AccessError::PermissionDenied {
+                id: 0,
+                permissions: Permissions::ReadOnly,
+                operation: Operation::Write,
+            })
+        );
+        assert!(user.read().is_ok());
+    }
+
+    #[test]
+    fn write_only_user_cannot_read() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::WriteOnly).unwrap();
+        assert_eq!(
+            user.read().err(),
+            Some(AccessError::PermissionDenied {
+                id: 0,
+                permissions: Permissions::WriteOnly,
+                operation: Operation::Read,
+            })
+        );
+        assert!(user.write().is_ok());
+    }
+
+    #[test]
+    fn revoked_user_is_denied_before_checking_permissions() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadOnly).unwrap();
+        owner.remove_user(0).unwrap();
+        assert_eq!(user.write().err(), Some(AccessError::Revoked { id: 0 }));
+    }
+
+    #[test]
+    fn read_only_user_ids_are_shared_with_other_users() {
+        let owner = Protected::new(42);
+        let user1 = owner.create_read_only_user(0);
+        let user2 = owner.create_user(0, Permissions::ReadWrite);
+        assert!(user1.is_ok());
+        assert_eq!(user2.err(), Some(AccessError::UserExists { id: 0 }));
+    }
+
+    #[test]
+    fn user_with_lease_can_read_before_expiry() {
+        let clock = ManualClock::new();
+        let owner = Protected::with_clock(42, clock.clone());
+        let user = owner
+            .create_user_for(0, Permissions::ReadWrite, Duration::from_secs(30))
+            .unwrap();
+        clock.advance(Duration::from_secs(29));
+        assert!(user.read().is_ok());
+    }
+
+    #[test]
+    fn user_with_expired_lease_cannot_read_or_write() {
+        let clock = ManualClock::new();
+        let owner = Protected::with_clock(42, clock.clone());
+        let user = owner
+            .create_user_for(0, Permissions::ReadWrite, Duration::from_secs(30))
+            .unwrap();
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(user.read().err(), Some(AccessError::Expired { id: 0 }));
+        assert_eq!(user.write().err(), Some(AccessError::Expired { id: 0 }));
+    }
+
+    #[test]
+    fn user_with_deadline_expires_at_deadline() {
+        let clock = ManualClock::new();
+        let owner = Protected::with_clock(42, clock.clone());
+        let deadline = clock.now() + Duration::from_secs(10);
+        let user = owner
+            .create_user_until(0, Permissions::ReadOnly, deadline)
+            .unwrap();
+        assert!(user.read().is_ok());
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(user.read().err(), Some(AccessError::Expired { id: 0 }));
+    }
+
+    #[test]
+    fn owner_can_renew_lease() {
+        let clock = ManualClock::new();
+        let owner = Protected::with_clock(42, clock.clone());
+        let user = owner
+            .create_user_for(0, Permissions::ReadWrite, Duration::from_secs(30))
+            .unwrap();
+        clock.advance(Duration::from_secs(20));
+        assert!(owner.renew(0, Duration::from_secs(30)).unwrap());
+        clock.advance(Duration::from_secs(20));
+        assert!(user.read().is_ok());
+    }
+
+    #[test]
+    fn owner_cannot_renew_expired_lease() {
+        let clock = ManualClock::new();
+        let owner = Protected::with_clock(42, clock.clone());
+        let user = owner
+            .create_user_for(0, Permissions::ReadWrite, Duration::from_secs(30))
+            .unwrap();
+        clock.advance(Duration::from_secs(30));
+        assert!(!owner.renew(0, Duration::from_secs(30)).unwrap());
+        assert!(!owner.renew(1, Duration::from_secs(30)).unwrap());
+        assert!(user.read().is_err());
+    }
+
+    #[test]
+    fn owner_can_create_user_with_expired_id() {
+        let clock = ManualClock::new();
+        let owner = Protected::with_clock(42, clock.clone());
+        let _user1 = owner
+            .create_user_for(0, Permissions::ReadWrite, Duration::from_secs(30))
+            .unwrap();
+        assert!(owner.create_user(0, Permissions::ReadWrite).is_err());
+        clock.advance(Duration::from_secs(30));
+        assert!(owner.create_user(0, Permissions::ReadWrite).is_ok());
+    }
+
+    #[test]
+    fn user_is_told_when_owner_was_dropped() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(7, Permissions::ReadWrite).unwrap();
+        drop(owner);
+        let error = user.read().err().unwrap();
+        assert_eq!(error, AccessError::OwnerDropped { id: 7 });
+        assert_eq!(error.id(), Some(7));
+    }
+
+    #[test]
+    fn poisoned_lock_is_reported_as_error() {
+        let owner = Arc::new(Protected::new(42));
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let poisoner = owner.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("poisoning the lock");
+        })
+        .join();
+
+        assert_eq!(owner.read().err(), Some(AccessError::Poisoned { id: None }));
+        assert_eq!(
+            user.write().err(),
+            Some(AccessError::Poisoned { id: Some(0) })
+        );
+        assert!(owner.create_user(1, Permissions::ReadWrite).is_err());
+        assert!(owner.remove_user(0).is_err());
+    }
+
+    #[test]
+    fn access_error_describes_the_denied_user() {
+        let error = AccessError::Revoked { id: 3 };
+        assert_eq!(error.to_string(), "user 3 has been revoked");
+        let error: Box<dyn std::error::Error> = Box::new(error);
+        assert!(error.source().is_none());
+    }
+
+    #[test]
+    fn owner_try_write_would_block_while_reading() {
+        let owner = Protected::new(42);
+        let _x = owner.read().unwrap();
+        assert_eq!(
+            owner.try_write().err(),
+            Some(AccessError::WouldBlock { id: None })
+        );
+        assert!(owner.try_read().is_ok());
+    }
+
+    #[test]
+    fn user_try_read_would_block_while_owner_writes() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let x = owner.write().unwrap();
+        assert_eq!(
+            user.try_read().err(),
+            Some(AccessError::WouldBlock { id: Some(0) })
+        );
+        drop(x);
+        assert_eq!(*user.try_read().unwrap(), 42);
+    }
+
+    #[test]
+    fn user_with_revoked_access_cannot_try_read_or_try_write() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        owner.remove_user(0).unwrap();
+        assert_eq!(user.try_read().err(), Some(AccessError::Revoked { id: 0 }));
+        assert_eq!(user.try_write().err(), Some(AccessError::Revoked { id: 0 }));
+    }
+
+    #[test]
+    fn write_timeout_gives_up_while_lock_is_held() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let _x = owner.read().unwrap();
+        assert_eq!(
+            user.write_timeout(Duration::from_millis(10)).err(),
+            Some(AccessError::TimedOut { id: Some(0) })
+        );
+        assert_eq!(
+            owner.write_timeout(Duration::from_millis(10)).err(),
+            Some(AccessError::TimedOut { id: None })
+        );
+    }
+
+    #[test]
+    fn read_timeout_succeeds_once_lock_is_released() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadOnly).unwrap();
+        std::thread::scope(|scope| {
+            let mut x = owner.write().unwrap();
+            let reader = scope.spawn(|| *user.read_timeout(Duration::from_secs(10)).unwrap());
+            std::thread::sleep(Duration::from_millis(50));
+            *x = 43;
+            drop(x);
+            assert_eq!(reader.join().unwrap(), 43);
+        });
+    }
+
+    #[test]
+    fn revoked_user_stays_revoked_when_its_id_is_reused() {
+        let owner = Protected::new(42);
+        let user1 = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        owner.remove_user(0).unwrap();
+        let user2 = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        assert_eq!(user1.read().err(), Some(AccessError::Revoked { id: 0 }));
+        assert_eq!(user1.write().err(), Some(AccessError::Revoked { id: 0 }));
+        assert!(user2.read().is_ok());
+    }
+
+    #[test]
+    fn expired_user_stays_denied_when_its_id_is_reused() {
+        let clock = ManualClock::new();
+        let owner = Protected::with_clock(42, clock.clone());
+        let user1 = owner
+            .create_user_for(0, Permissions::ReadWrite, Duration::from_secs(30))
+            .unwrap();
+        clock.advance(Duration::from_secs(30));
+        let user2 = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        assert!(user1.read().is_err());
+        assert!(user2.read().is_ok());
+    }
+
+    #[test]
+    fn dropping_revoked_user_does_not_revoke_user_with_reused_id() {
+        let owner = Protected::new(42);
+        let user1 = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        owner.remove_user(0).unwrap();
+        let user2 = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        drop(user1);
+        assert!(user2.read().is_ok());
+        assert!(owner.create_user(0, Permissions::ReadWrite).is_err());
+    }
+
+    #[test]
+    fn owner_can_count_guards_held_by_user() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadOnly).unwrap();
+        let x = user.read().unwrap();
+        let y = user.try_read().unwrap();
+        let _z = owner.read().unwrap();
+        assert_eq!(owner.guards_held_by(0).unwrap(), 2);
+        drop((x, y));
+        assert_eq!(owner.guards_held_by(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn revoke_and_wait_returns_after_user_guards_are_dropped() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let done = std::sync::atomic::AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let mut x = user.write().unwrap();
+            scope.spawn(|| {
+                assert!(owner.revoke_and_wait(0).unwrap());
+                assert!(done.load(Ordering::SeqCst));
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            *x = 43;
+            done.store(true, Ordering::SeqCst);
+        });
+        assert_eq!(user.read().err(), Some(AccessError::Revoked { id: 0 }));
+        assert_eq!(*owner.read().unwrap(), 43);
+    }
+
+    #[test]
+    fn revoke_and_wait_timeout_gives_up_while_user_holds_guard() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let x = user.read().unwrap();
+        assert_eq!(
+            owner.revoke_and_wait_timeout(0, Duration::from_millis(10)),
+            Err(AccessError::TimedOut { id: None })
+        );
+        assert_eq!(*user.try_read().unwrap(), 42);
+        drop(x);
+        assert!(owner
+            .revoke_and_wait_timeout(0, Duration::from_millis(10))
+            .unwrap());
+        assert!(!owner.revoke_and_wait(0).unwrap());
+    }
+
+    #[test]
+    fn revoke_and_wait_timeout_ignores_guards_of_other_users() {
+        let owner = Protected::new(42);
+        let _user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let other = owner.create_user(1, Permissions::ReadWrite).unwrap();
+        let x = other.read().unwrap();
+        assert!(owner
+            .revoke_and_wait_timeout(0, Duration::from_millis(50))
+            .unwrap());
+        assert_eq!(*x, 42);
+        assert_eq!(owner.guards_held_by(1).unwrap(), 1);
+    }
+
+    #[test]
+    fn user_being_revoked_gets_no_new_guard() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let child = user.delegate(1, Permissions::ReadOnly).unwrap();
+        std::thread::scope(|scope| {
+            let x = user.read().unwrap();
+            let revoker = scope.spawn(|| owner.revoke_and_wait(0));
+            while user.try_read().is_ok() {
+                std::thread::yield_now();
+            }
+            assert_eq!(user.try_read().err(), Some(AccessError::Revoked { id: 0 }));
+            assert_eq!(child.try_read().err(), Some(AccessError::Revoked { id: 1 }));
+            drop(x);
+            assert_eq!(revoker.join().unwrap(), Ok(true));
+        });
+    }
+
+    #[test]
+    fn observer_is_told_about_every_event() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let owner = Protected::new(42);
+        let recorded = events.clone();
+        owner.add_observer(move |event: &AuditEvent| {
+            recorded.lock().unwrap().push(event.kind.clone());
+        });
+
+        let user1 = owner.create_user(0, Permissions::ReadOnly).unwrap();
+        let user2 = owner.create_user(1, Permissions::ReadWrite).unwrap();
+        drop(user1.read().unwrap());
+        assert!(user1.write().is_err());
+        drop(owner.write().unwrap());
+        owner.remove_user(0).unwrap();
+        drop(user2);
+        drop(owner);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                AuditEventKind::UserCreated {
+                    id: 0,
+                    permissions: Permissions::ReadOnly
+                },
+                AuditEventKind::UserCreated {
+                    id: 1,
+                    permissions: Permissions::ReadWrite
+                },
+                AuditEventKind::Granted {
+                    id: Some(0),
+                    operation: Operation::Read
+                },
+                AuditEventKind::Denied {
+                    id: 0,
+                    operation: Operation::Write,
+                    error: AccessError::PermissionDenied {
+                        id: 0,
+                        permissions: Permissions::ReadOnly,
+                        operation: Operation::Write
+                    }
+                },
+                AuditEventKind::Granted {
+                    id: None,
+                    operation: Operation::Write
+                },
+                AuditEventKind::UserRemoved { id: 0 },
+                AuditEventKind::UserDropped { id: 1 },
+                AuditEventKind::OwnerDropped,
+            ]
+        );
+    }
+
+    #[test]
+    fn audit_log_keeps_most_recent_events() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        assert!(owner.audit_log().is_empty());
+
+        owner.set_audit_log_capacity(2);
+        drop(user.read().unwrap());
+        owner.remove_user(0).unwrap();
+        assert!(user.write().is_err());
+
+        let kinds: Vec<_> = owner
+            .audit_log()
+            .into_iter()
+            .map(|event| event.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                AuditEventKind::UserRemoved { id: 0 },
+                AuditEventKind::Denied {
+                    id: 0,
+                    operation: Operation::Write,
+                    error: AccessError::Revoked { id: 0 }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn audit_log_timestamps_are_ordered() {
+        let owner = Protected::new(42);
+        owner.set_audit_log_capacity(16);
+        for _ in 0..4 {
+            drop(owner.read().unwrap());
+        }
+        let log = owner.audit_log();
+        assert_eq!(log.len(), 4);
+        assert!(log
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_count_accesses_per_user() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadOnly).unwrap();
+        drop(owner.write().unwrap());
+        drop(user.read().unwrap());
+        drop(user.read().unwrap());
+        assert!(user.write().is_err());
+
+        let stats = owner.stats();
+        assert_eq!((stats.owner.reads, stats.owner.writes), (0, 1));
+        let user_stats = stats.users[&0];
+        assert_eq!(
+            (user_stats.reads, user_stats.writes, user_stats.denials),
+            (2, 0, 1)
+        );
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_count_failed_waits() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let x = owner.write().unwrap();
+        assert!(user.try_read().is_err());
+        assert!(user.write_timeout(Duration::from_millis(20)).is_err());
+        drop(x);
+
+        let user_stats = owner.stats().users[&0];
+        assert_eq!(
+            (user_stats.reads, user_stats.writes, user_stats.timeouts),
+            (0, 0, 2)
+        );
+        assert!(user_stats.wait_time >= Duration::from_millis(20));
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_forget_users_once_they_are_gone() {
+        let owner = Protected::new(42);
+        for _ in 0..1000 {
+            let (user, _) = owner.create_anonymous_user(Permissions::ReadOnly).unwrap();
+            drop(user.read().unwrap());
+        }
+        let user = owner.create_user(0, Permissions::ReadOnly).unwrap();
+        let child = user.delegate(1, Permissions::ReadOnly).unwrap();
+        drop(child.read().unwrap());
+        assert_eq!(owner.stats().users.len(), 2);
+
+        owner.remove_user(0).unwrap();
+        assert!(owner.stats().users.is_empty());
+        assert!(child.read().is_err());
+        assert!(owner.stats().users.is_empty());
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_measure_hold_time() {
+        let owner = Protected::new(42);
+        let user = owner.create_read_only_user(0).unwrap();
+        let x = user.read().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        drop(x);
+        let stats = owner.stats();
+        assert!(stats.users[&0].hold_time >= Duration::from_millis(20));
+        assert_eq!(stats.owner.hold_time, Duration::ZERO);
+    }
+
+    #[test]
+    fn anonymous_user_ids_are_never_reused() {
+        let owner = Protected::new(42);
+        let _explicit = owner.create_read_only_user(1).unwrap();
+        let (first, first_id) = owner.create_anonymous_user(Permissions::ReadOnly).unwrap();
+        let (_second, second_id) = owner.create_anonymous_user(Permissions::ReadOnly).unwrap();
+        assert_eq!((first_id, second_id), (0, 2));
+
+        drop(first);
+        let (_third, third_id) = owner.create_anonymous_user(Permissions::ReadOnly).unwrap();
+        assert_eq!(third_id, 3);
+    }
+
+    #[test]
+    fn owner_can_revoke_anonymous_user() {
+        let owner = Protected::new(42);
+        let (user, id) = owner.create_anonymous_user(Permissions::ReadWrite).unwrap();
+        assert!(user.write().is_ok());
+        owner.remove_user(id).unwrap();
+        assert_eq!(user.read().err(), Some(AccessError::Revoked { id }));
+    }
+
+    #[test]
+    fn anonymous_user_ids_can_run_out() {
+        let owner = Protected::new(42);
+        owner.write_control().unwrap().next_anonymous_id = Some(u32::MAX);
+        let (_last, id) = owner.create_anonymous_user(Permissions::ReadOnly).unwrap();
+        assert_eq!(id, u32::MAX);
+        assert_eq!(
+            owner.create_anonymous_user(Permissions::ReadOnly).err(),
+            Some(AccessError::IdsExhausted)
+        );
+    }
+
+    #[test]
+    fn user_can_delegate_fewer_permissions() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let helper = user.delegate(1, Permissions::ReadOnly).unwrap();
+        assert_eq!(*helper.read().unwrap(), 42);
+        assert!(helper.write().is_err());
+        assert_eq!(
+            helper.delegate(2, Permissions::ReadWrite).err(),
+            Some(AccessError::PermissionsExceeded {
+                id: 1,
+                permissions: Permissions::ReadOnly,
+                requested: Permissions::ReadWrite,
+            })
+        );
+        assert_eq!(
+            user.delegate(1, Permissions::ReadOnly).err(),
+            Some(AccessError::UserExists { id: 1 })
+        );
+    }
+
+    #[test]
+    fn removing_user_revokes_its_descendants() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let child = user.delegate(1, Permissions::ReadWrite).unwrap();
+        let grandchild = child.delegate(2, Permissions::ReadOnly).unwrap();
+        let other = owner.create_read_only_user(3).unwrap();
+
+        owner.remove_user(1).unwrap();
+        assert!(user.read().is_ok());
+        assert_eq!(child.read().err(), Some(AccessError::Revoked { id: 1 }));
+        assert_eq!(
+            grandchild.read().err(),
+            Some(AccessError::Revoked { id: 2 })
+        );
+        assert!(other.read().is_ok());
+    }
+
+    #[test]
+    fn dropping_user_revokes_its_descendants() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let child = user.delegate(1, Permissions::ReadOnly).unwrap();
+        drop(user);
+        assert_eq!(child.read().err(), Some(AccessError::Revoked { id: 1 }));
+        assert!(owner.create_read_only_user(1).is_ok());
+    }
+
+    #[test]
+    fn revoked_user_cannot_delegate() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        owner.remove_user(0).unwrap();
+        assert_eq!(
+            user.delegate(1, Permissions::ReadOnly).err(),
+            Some(AccessError::Revoked { id: 0 })
+        );
+    }
+
+    #[test]
+    fn sub_users_are_bound_by_the_lease_of_their_parent() {
+        let clock = ManualClock::new();
+        let owner = Protected::with_clock(42, clock.clone());
+        let user = owner
+            .create_user_for(0, Permissions::ReadWrite, Duration::from_secs(10))
+            .unwrap();
+        let child = user.delegate(1, Permissions::ReadOnly).unwrap();
+        clock.advance(Duration::from_secs(5));
+        assert!(owner.renew(0, Duration::from_secs(10)).unwrap());
+        clock.advance(Duration::from_secs(6));
+        assert!(child.read().is_ok());
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(child.read().err(), Some(AccessError::Expired { id: 1 }));
+    }
+
+    #[test]
+    fn owner_can_inspect_delegation_tree() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let _first = user.delegate(1, Permissions::ReadOnly).unwrap();
+        let _second = user.delegate(2, Permissions::WriteOnly).unwrap();
+
+        let mut sub_users = owner.sub_users_of(0).unwrap();
+        sub_users.sort();
+        assert_eq!(sub_users, vec![1, 2]);
+        assert_eq!(owner.sub_users_of(1).unwrap(), Vec::<u32>::new());
+        assert_eq!(owner.parent_of(2).unwrap(), Some(0));
+        assert_eq!(owner.parent_of(0).unwrap(), None);
+    }
+
+    #[test]
+    fn revoke_and_wait_waits_for_descendants() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let child = user.delegate(1, Permissions::ReadOnly).unwrap();
+        std::thread::scope(|scope| {
+            let guard = child.read().unwrap();
+            let revoker = scope.spawn(|| owner.revoke_and_wait(0));
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(!revoker.is_finished());
+            drop(guard);
+            assert_eq!(revoker.join().unwrap(), Ok(true));
+        });
+        assert_eq!(child.read().err(), Some(AccessError::Revoked { id: 1 }));
+    }
+
+    #[test]
+    fn revoking_group_cuts_off_its_members() {
+        let owner = Protected::new(42);
+        let alice = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let bob = owner.create_user(1, Permissions::ReadWrite).unwrap();
+        let carol = owner.create_user(2, Permissions::ReadWrite).unwrap();
+        owner
+            .grant_group("maintenance", Permissions::ReadWrite)
+            .unwrap();
+        assert!(owner.add_to_group(0, "maintenance").unwrap());
+        assert!(owner.add_to_group(1, "maintenance").unwrap());
+        assert!(alice.write().is_ok());
+
+        assert!(owner.revoke_group("maintenance").unwrap());
+        assert_eq!(
+            alice.read().err(),
+            Some(AccessError::GroupsDenied { id: 0 })
+        );
+        assert_eq!(bob.write().err(), Some(AccessError::GroupsDenied { id: 1 }));
+        assert!(carol.write().is_ok());
+
+        owner
+            .grant_group("maintenance", Permissions::ReadOnly)
+            .unwrap();
+        assert!(alice.read().is_ok());
+        assert!(alice.write().is_err());
+    }
+
+    #[test]
+    fn group_permissions_are_combined_and_capped() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadOnly).unwrap();
+        owner.grant_group("readers", Permissions::ReadOnly).unwrap();
+        owner
+            .grant_group("writers", Permissions::WriteOnly)
+            .unwrap();
+        owner.add_to_group(0, "writers").unwrap();
+        assert_eq!(user.read().err(), Some(AccessError::GroupsDenied { id: 0 }));
+
+        owner.add_to_group(0, "readers").unwrap();
+        assert!(user.read().is_ok());
+        assert_eq!(
+            user.write().err(),
+            Some(AccessError::PermissionDenied {
+                id: 0,
+                permissions: Permissions::ReadOnly,
+                operation: Operation::Write,
+            })
+        );
+    }
+
+    #[test]
+    fn group_membership_is_tracked_per_user() {
+        let owner = Protected::new(42);
+        let _user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        assert!(!owner.add_to_group(0, "maintenance").unwrap());
+        owner
+            .grant_group("maintenance", Permissions::ReadWrite)
+            .unwrap();
+        assert!(!owner.add_to_group(1, "maintenance").unwrap());
+        assert!(owner.add_to_group(0, "maintenance").unwrap());
+        assert_eq!(owner.groups_of(0).unwrap(), vec!["maintenance"]);
+        assert_eq!(owner.members_of("maintenance").unwrap(), vec![0]);
+
+        assert!(owner.remove_from_group(0, "maintenance").unwrap());
+        assert!(!owner.remove_from_group(0, "maintenance").unwrap());
+        assert!(owner.members_of("maintenance").unwrap().is_empty());
+    }
+
+    #[test]
+    fn removed_users_leave_their_groups() {
+        let owner = Protected::new(42);
+        let _user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        owner
+            .grant_group("maintenance", Permissions::ReadWrite)
+            .unwrap();
+        owner.add_to_group(0, "maintenance").unwrap();
+        owner.remove_user(0).unwrap();
+        let _user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        assert!(owner.groups_of(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn sub_users_lose_access_with_the_groups_of_their_parent() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        owner
+            .grant_group("maintenance", Permissions::ReadWrite)
+            .unwrap();
+        owner.add_to_group(0, "maintenance").unwrap();
+        let helper = user.delegate(1, Permissions::ReadOnly).unwrap();
+        owner.revoke_group("maintenance").unwrap();
+        assert_eq!(
+            helper.read().err(),
+            Some(AccessError::GroupsDenied { id: 1 })
+        );
+    }
+
+    #[test]
+    fn transferred_ownership_keeps_users() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let owner = std::thread::spawn(move || owner.transfer_ownership())
+            .join()
+            .unwrap();
+        assert!(user.write().is_ok());
+        assert_eq!(owner.owner_count().unwrap(), 1);
+
+        owner.remove_user(0).unwrap();
+        assert_eq!(user.read().err(), Some(AccessError::Revoked { id: 0 }));
+    }
+
+    #[test]
+    fn users_are_revoked_when_the_last_owner_is_dropped() {
+        let owner = Protected::new(42);
+        let co_owner = owner.create_co_owner().unwrap();
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        assert_eq!(co_owner.owner_count().unwrap(), 2);
+        assert!(co_owner.create_user(1, Permissions::ReadOnly).is_ok());
+
+        drop(owner);
+        assert!(user.read().is_ok());
+        *co_owner.write().unwrap() = 43;
+
+        drop(co_owner);
+        assert_eq!(user.read().err(), Some(AccessError::OwnerDropped { id: 0 }));
+    }
+
+    #[test]
+    fn co_owners_are_audited() {
+        let owner = Protected::new(42);
+        owner.set_audit_log_capacity(16);
+        let co_owner = owner.create_co_owner().unwrap();
+        let co_owner = co_owner.transfer_ownership();
+        drop(co_owner);
+        let kinds: Vec<_> = owner
+            .audit_log()
+            .into_iter()
+            .map(|event| event.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                AuditEventKind::CoOwnerCreated,
+                AuditEventKind::CoOwnerCreated,
+                AuditEventKind::CoOwnerDropped,
+                AuditEventKind::CoOwnerDropped,
+            ]
+        );
+    }
+
+    /// Value that flags when it is dropped.
+    struct DropFlag(Arc<std::sync::atomic::AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
         }
-        let x = user2.read().unwrap();
+    }
+
+    #[test]
+    fn destroy_value_policy_drops_value_with_last_owner() {
+        let dropped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let owner = Protected::builder(DropFlag(dropped.clone()))
+            .owner_drop_policy(OwnerDropPolicy::DestroyValue)
+            .build();
+        let user = owner.create_read_only_user(0).unwrap();
+        let co_owner = owner.create_co_owner().unwrap();
+        drop(owner);
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        drop(co_owner);
+        assert!(dropped.load(Ordering::SeqCst));
+        let error = user.read().err().unwrap();
+        assert_eq!(error, AccessError::Destroyed { id: Some(0) });
+        assert_eq!(
+            error.to_string(),
+            "the value user 0 had access to has been destroyed"
+        );
+    }
+
+    #[test]
+    fn revoke_users_policy_keeps_value_until_last_user() {
+        let dropped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let owner = Protected::new(DropFlag(dropped.clone()));
+        let user = owner.create_read_only_user(0).unwrap();
+        drop(owner);
+        assert_eq!(user.read().err(), Some(AccessError::OwnerDropped { id: 0 }));
+        assert!(!dropped.load(Ordering::SeqCst));
+        drop(user);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn keep_users_policy_lets_users_outlive_owner() {
+        let owner = Protected::builder(42)
+            .owner_drop_policy(OwnerDropPolicy::KeepUsers)
+            .build();
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        drop(owner);
+        *user.write().unwrap() = 43;
+        assert_eq!(*user.read().unwrap(), 43);
+    }
+
+    #[test]
+    fn builder_configures_clock_and_ids() {
+        let clock = ManualClock::new();
+        let owner = Protected::builder(42)
+            .user_ids::<String>()
+            .clock(clock.clone())
+            .build();
+        let user = owner
+            .create_user_for(
+                "alice".to_string(),
+                Permissions::ReadOnly,
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        assert!(user.read().is_ok());
+        clock.advance(Duration::from_secs(1));
+        assert!(user.read().is_err());
+    }
+
+    #[test]
+    fn destroyed_value_is_dropped_and_denied() {
+        let dropped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let owner = Protected::new(DropFlag(dropped.clone()));
+        let user = owner.create_read_only_user(0).unwrap();
+        owner.destroy();
+        assert!(dropped.load(Ordering::SeqCst));
+        assert_eq!(
+            user.read().err(),
+            Some(AccessError::Destroyed { id: Some(0) })
+        );
+        assert_eq!(
+            owner.write().err(),
+            Some(AccessError::Destroyed { id: None })
+        );
+    }
+
+    #[test]
+    fn debug_output_never_shows_handles_value() {
+        let owner = Protected::new(42);
+        let user = owner.create_read_only_user(7).unwrap();
+        assert_eq!(format!("{owner:?}"), "Protected { id: None, .. }");
+        assert_eq!(format!("{user:?}"), "Protected { id: Some(7), .. }");
+        assert_eq!(format!("{:?}", user.read().unwrap()), "42");
+    }
+
+    #[cfg(feature = "secret")]
+    mod secret {
+        use std::sync::Mutex;
+
+        use super::*;
+
+        /// Key material whose bytes remain observable after it has been dropped.
+        struct Key(Arc<Mutex<Vec<u8>>>);
+
+        impl zeroize::Zeroize for Key {
+            fn zeroize(&mut self) {
+                self.0.lock().unwrap().zeroize();
+            }
+        }
+
+        impl fmt::Debug for Key {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.lock().unwrap().fmt(f)
+            }
+        }
+
+        fn secret() -> (Protected<Key, Owner>, Arc<Mutex<Vec<u8>>>) {
+            let bytes = Arc::new(Mutex::new(vec![1, 2, 3]));
+            let owner = Protected::builder(Key(bytes.clone())).secret().build();
+            (owner, bytes)
+        }
+
+        #[test]
+        fn destroy_wipes_secret() {
+            let (owner, bytes) = secret();
+            let user = owner.create_read_only_user(0).unwrap();
+            assert_eq!(*user.read().unwrap().0.lock().unwrap(), [1, 2, 3]);
+            owner.destroy();
+            assert!(bytes.lock().unwrap().is_empty());
+            assert_eq!(
+                user.read().err(),
+                Some(AccessError::Destroyed { id: Some(0) })
+            );
+        }
+
+        #[test]
+        fn dropping_owner_wipes_secret() {
+            let (owner, bytes) = secret();
+            let user = owner.create_read_only_user(0).unwrap();
+            drop(owner);
+            assert!(bytes.lock().unwrap().is_empty());
+            assert!(user.read().is_err());
+        }
+
+        #[test]
+        fn dropping_owner_wipes_secret_whatever_the_policy() {
+            for policy in [OwnerDropPolicy::RevokeUsers, OwnerDropPolicy::KeepUsers] {
+                let bytes = Arc::new(Mutex::new(vec![1, 2, 3]));
+                let owner = Protected::builder(Key(bytes.clone()))
+                    .secret()
+                    .owner_drop_policy(policy)
+                    .build();
+                let user = owner.create_read_only_user(0).unwrap();
+                drop(owner);
+                assert!(bytes.lock().unwrap().is_empty());
+                assert_eq!(
+                    user.read().err(),
+                    Some(AccessError::Destroyed { id: Some(0) })
+                );
+            }
+        }
+
+        /// Key material stored inline, which records where it has been wiped.
+        struct InlineKey([u8; 32]);
+
+        thread_local! {
+            static WIPED_AT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+
+        impl zeroize::Zeroize for InlineKey {
+            fn zeroize(&mut self) {
+                self.0.zeroize();
+                WIPED_AT.set(self as *mut InlineKey as usize);
+            }
+        }
+
+        #[test]
+        fn destroy_wipes_inline_secret_in_place() {
+            let owner = Protected::builder(InlineKey([7; 32])).secret().build();
+            let stored_at = {
+                let key = owner.read().unwrap();
+                &*key as *const InlineKey as usize
+            };
+            owner.destroy();
+            assert_eq!(WIPED_AT.get(), stored_at);
+        }
+
+        #[test]
+        fn secret_guards_are_redacted() {
+            let (owner, _bytes) = secret();
+            assert_eq!(format!("{:?}", owner.read().unwrap()), "<redacted>");
+            assert_eq!(format!("{:?}", owner.write().unwrap()), "<redacted>");
+        }
+
+        #[test]
+        fn mapped_secret_guards_are_redacted() {
+            let (owner, _bytes) = secret();
+            let x = ProtectedReadGuard::map(owner.read().unwrap(), |key| &key.0);
+            assert_eq!(format!("{x:?}"), "<redacted>");
+            drop(x);
+            let x = ProtectedWriteGuard::map(owner.write().unwrap(), |key| &mut key.0);
+            assert_eq!(format!("{x:?}"), "<redacted>");
+        }
+    }
+
+    #[derive(Debug)]
+    struct Config {
+        name: String,
+        ports: Vec<u16>,
+    }
+
+    fn config() -> Protected<Config, Owner> {
+        Protected::new(Config {
+            name: "server".to_string(),
+            ports: vec![80, 443],
+        })
+    }
+
+    /// Returns a guard to the name only, as functions using mapped guards would.
+    fn name_of(config: &Protected<Config, User>) -> MappedProtectedReadGuard<'_, Config, str> {
+        ProtectedReadGuard::map(config.read().unwrap(), |config| config.name.as_str())
+    }
+
+    #[test]
+    fn mapped_read_guard_keeps_lock_held() {
+        let owner = config();
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let name = name_of(&user);
+        assert_eq!(&*name, "server");
+        assert_eq!(format!("{name:?}"), "\"server\"");
+        assert!(matches!(
+            owner.try_write(),
+            Err(AccessError::WouldBlock { id: None })
+        ));
+        drop(name);
+        assert!(owner.try_write().is_ok());
+    }
+
+    #[test]
+    fn mapped_write_guard_modifies_part_of_value() {
+        let owner = config();
+        let guard = owner.write().unwrap();
+        let mut ports = ProtectedWriteGuard::map(guard, |config| &mut config.ports);
+        ports.push(8080);
+        assert_eq!(format!("{ports:?}"), "[80, 443, 8080]");
+        let mut last = MappedProtectedWriteGuard::map(ports, |ports| ports.last_mut().unwrap());
+        *last += 1;
+        drop(last);
+        assert_eq!(owner.read().unwrap().ports, [80, 443, 8081]);
+    }
+
+    #[test]
+    fn try_map_hands_back_original_guard() {
+        let owner = config();
+        let guard = owner.read().unwrap();
+        let guard = ProtectedReadGuard::try_map(guard, |config| config.ports.get(2)).unwrap_err();
+        let port = ProtectedReadGuard::try_map(guard, |config| config.ports.get(1)).unwrap();
+        assert_eq!(*port, 443);
+        drop(port);
+
+        let guard = owner.write().unwrap();
+        let guard =
+            ProtectedWriteGuard::try_map(guard, |config| config.ports.get_mut(2)).unwrap_err();
+        let mut port =
+            ProtectedWriteGuard::try_map(guard, |config| config.ports.get_mut(0)).unwrap();
+        *port = 8000;
+        drop(port);
+        assert_eq!(owner.read().unwrap().ports, [8000, 443]);
+    }
+
+    #[test]
+    fn upgradable_read_excludes_writers_but_not_readers() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let x = user.upgradable_read().unwrap();
+        assert_eq!(*x, 42);
+        assert_eq!(*owner.try_read().unwrap(), 42);
+        assert_eq!(
+            owner.try_write().err(),
+            Some(AccessError::WouldBlock { id: None })
+        );
+        assert_eq!(
+            owner.try_upgradable_read().err(),
+            Some(AccessError::WouldBlock { id: None })
+        );
+        drop(x);
+        assert!(owner.try_upgradable_read().is_ok());
+    }
+
+    #[test]
+    fn upgrade_lets_no_writer_in() {
+        let owner = Protected::new(vec![]);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        std::thread::scope(|s| {
+            let x = owner.upgradable_read().unwrap();
+            let writer = s.spawn(|| user.write().unwrap().push("writer"));
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(x.is_empty());
+            let mut x = ProtectedUpgradableReadGuard::upgrade(x).unwrap();
+            x.push("upgraded");
+            drop(x);
+            writer.join().unwrap();
+        });
+        assert_eq!(*owner.read().unwrap(), ["upgraded", "writer"]);
+    }
+
+    #[test]
+    fn upgrade_checks_access_again() {
+        let clock = ManualClock::new();
+        let owner = Protected::with_clock(42, clock.clone());
+        let user = owner
+            .create_user_for(0, Permissions::ReadWrite, Duration::from_secs(30))
+            .unwrap();
+        let x = user.upgradable_read().unwrap();
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(
+            ProtectedUpgradableReadGuard::upgrade(x).err(),
+            Some(AccessError::Expired { id: 0 })
+        );
+        assert!(owner.try_write().is_ok());
+
+        let user = owner.create_user(1, Permissions::ReadOnly).unwrap();
+        let x = user.upgradable_read().unwrap();
+        assert_eq!(
+            ProtectedUpgradableReadGuard::upgrade(x).err(),
+            Some(AccessError::PermissionDenied {
+                id: 1,
+                permissions: Permissions::ReadOnly,
+                operation: Operation::Write,
+            })
+        );
+    }
+
+    #[test]
+    fn downgrade_lets_readers_in() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let mut x = user.write().unwrap();
+        *x += 1;
+        let x = ProtectedWriteGuard::downgrade(x);
         assert_eq!(*x, 43);
+        assert_eq!(*owner.try_read().unwrap(), 43);
+        assert_eq!(
+            owner.try_write().err(),
+            Some(AccessError::WouldBlock { id: None })
+        );
+        drop(x);
+        assert!(owner.try_write().is_ok());
+    }
+
+    #[test]
+    fn readers_wait_behind_waiting_writers() {
+        let owner = Protected::new(0);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        std::thread::scope(|s| {
+            let x = owner.read().unwrap();
+            let writer = s.spawn(|| *user.write().unwrap() += 1);
+            std::thread::sleep(Duration::from_millis(50));
+            assert_eq!(
+                owner.try_read().err(),
+                Some(AccessError::WouldBlock { id: None })
+            );
+            drop(x);
+            writer.join().unwrap();
+        });
+        assert_eq!(*owner.try_read().unwrap(), 1);
+    }
+
+    #[test]
+    fn guards_can_be_dropped_by_another_thread() {
+        let owner = Protected::new(42);
+        std::thread::scope(|s| {
+            let mut x = owner.write().unwrap();
+            *x += 1;
+            s.spawn(move || drop(x));
+        });
+        assert_eq!(*owner.try_read().unwrap(), 43);
+    }
+
+    #[test]
+    fn snapshots_are_not_affected_by_later_versions() {
+        let owner = Protected::new(Arc::new(vec![1]));
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let reader = owner.create_read_only_user(1).unwrap();
+        let first = reader.snapshot().unwrap();
+        // Snapshots do not hold the lock.
+        assert!(owner.try_write().is_ok());
+        user.publish(vec![1, 2]).unwrap();
+        let third = owner.update(|v| [v.as_slice(), &[3]].concat()).unwrap();
+        assert_eq!(*first, [1]);
+        assert_eq!(*third, [1, 2, 3]);
+        assert!(Arc::ptr_eq(&reader.snapshot().unwrap(), &third));
+    }
+
+    #[test]
+    fn snapshots_are_gated_by_access_keys() {
+        let owner = Protected::new(Arc::new(42));
+        let user = owner.create_user(0, Permissions::ReadOnly).unwrap();
+        let snapshot = user.snapshot().unwrap();
+        assert_eq!(
+            user.publish(43).err(),
+            Some(AccessError::PermissionDenied {
+                id: 0,
+                permissions: Permissions::ReadOnly,
+                operation: Operation::Write,
+            })
+        );
+        owner.remove_user(0).unwrap();
+        assert_eq!(user.snapshot().err(), Some(AccessError::Revoked { id: 0 }));
+        assert_eq!(*snapshot, 42);
+    }
+
+    #[test]
+    fn snapshots_do_not_wait_for_the_lock() {
+        let owner = Protected::new(Arc::new(42));
+        let reader = owner.create_read_only_user(0).unwrap();
+        let first = reader.snapshot().unwrap();
+        let x = owner.write().unwrap();
+        assert!(Arc::ptr_eq(&reader.snapshot().unwrap(), &first));
+        drop(x);
+        owner.remove_user(0).unwrap();
+        assert_eq!(
+            reader.snapshot().err(),
+            Some(AccessError::Revoked { id: 0 })
+        );
+    }
+
+    #[test]
+    fn snapshots_do_not_keep_previous_versions_alive() {
+        let owner = Protected::new(Arc::new(1));
+        let first = owner.snapshot().unwrap();
+        owner.publish(2).unwrap();
+        assert_eq!(Arc::strong_count(&first), 1);
+        let second = owner.snapshot().unwrap();
+        assert_eq!(*second, 2);
+        owner.destroy();
+        assert_eq!(Arc::strong_count(&second), 1);
+    }
+
+    #[test]
+    fn snapshots_race_with_updates() {
+        const UPDATES: usize = 50;
+        let owner = Protected::new(Arc::new(0));
+        let readers: Vec<_> = (0..2)
+            .map(|id| owner.create_read_only_user(id).unwrap())
+            .collect();
+        std::thread::scope(|s| {
+            for reader in &readers {
+                s.spawn(move || {
+                    let mut last = 0;
+                    while last < UPDATES {
+                        let snapshot = *reader.snapshot().unwrap();
+                        assert!(snapshot >= last);
+                        last = snapshot;
+                    }
+                });
+            }
+            for _ in 0..UPDATES {
+                owner.update(|value| value + 1).unwrap();
+            }
+        });
+        assert_eq!(*owner.snapshot().unwrap(), UPDATES);
+    }
+
+    #[test]
+    fn update_retries_on_concurrent_publish() {
+        let owner = Protected::new(Arc::new(0));
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let mut calls = 0;
+        let updated = owner
+            .update(|value| {
+                calls += 1;
+                if calls == 1 {
+                    user.publish(10).unwrap();
+                }
+                value + 1
+            })
+            .unwrap();
+        assert_eq!(calls, 2);
+        assert_eq!(*updated, 11);
+        assert_eq!(*user.snapshot().unwrap(), 11);
+    }
+
+    #[test]
+    fn retried_updates_are_not_counted_as_writes() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let owner = Protected::new(Arc::new(0));
+        let recorded = events.clone();
+        owner.add_observer(move |event: &AuditEvent| {
+            if let AuditEventKind::Granted {
+                operation: Operation::Write,
+                id,
+            } = event.kind
+            {
+                recorded.lock().unwrap().push(id);
+            }
+        });
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let mut calls = 0;
+        user.update(|value| {
+            calls += 1;
+            if calls == 1 {
+                owner.publish(10).unwrap();
+            }
+            value + 1
+        })
+        .unwrap();
+        assert_eq!(calls, 2);
+        assert_eq!(*events.lock().unwrap(), [None, Some(0)]);
+        assert_eq!(owner.version(), 2);
+        #[cfg(feature = "stats")]
+        assert_eq!(owner.stats().users[&0].writes, 1);
+    }
+
+    #[test]
+    fn version_is_bumped_by_write_guards_only() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        assert_eq!(user.version(), 0);
+        drop(owner.read().unwrap());
+        drop(user.upgradable_read().unwrap());
+        owner.create_user(1, Permissions::ReadOnly).unwrap();
+        assert_eq!(user.version(), 0);
+        *user.write().unwrap() += 1;
+        assert_eq!(owner.version(), 1);
+        drop(ProtectedWriteGuard::downgrade(owner.write().unwrap()));
+        assert_eq!(owner.version(), 2);
+        assert_eq!(user.wait_for_change(0), Ok(2));
+    }
+
+    #[test]
+    fn wait_for_change_returns_once_value_is_written() {
+        let owner = Protected::new(42);
+        let user = owner.create_read_only_user(0).unwrap();
+        let version = user.version();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                *owner.write().unwrap() = 43;
+            });
+            assert_eq!(user.wait_for_change(version), Ok(version + 1));
+        });
+        assert_eq!(*user.read().unwrap(), 43);
+        assert_eq!(
+            user.wait_for_change_timeout(version + 1, Duration::from_millis(10)),
+            Err(AccessError::TimedOut { id: Some(0) })
+        );
+    }
+
+    #[test]
+    fn wait_for_change_stops_when_user_is_revoked() {
+        let owner = Protected::new(42);
+        let user = owner.create_read_only_user(0).unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                owner.remove_user(0).unwrap();
+            });
+            let start = Instant::now();
+            assert_eq!(
+                user.wait_for_change_timeout(user.version(), Duration::from_secs(10)),
+                Err(AccessError::Revoked { id: 0 })
+            );
+            assert!(start.elapsed() < Duration::from_secs(5));
+        });
+    }
+
+    #[test]
+    fn wait_until_returns_guard_once_predicate_holds() {
+        let owner = Protected::new(0);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for _ in 0..3 {
+                    std::thread::sleep(Duration::from_millis(10));
+                    *user.write().unwrap() += 1;
+                }
+            });
+            let x = owner.wait_until(|x| *x == 3).unwrap();
+            assert_eq!(*x, 3);
+        });
+        assert_eq!(
+            user.wait_until_timeout(|x| *x > 3, Duration::from_millis(10))
+                .err(),
+            Some(AccessError::TimedOut { id: Some(0) })
+        );
+    }
+
+    #[test]
+    fn views_only_access_their_part_of_the_value() {
+        let owner = config();
+        let view = owner
+            .create_view(
+                0,
+                Permissions::ReadWrite,
+                |config| &config.ports,
+                |config| &mut config.ports,
+            )
+            .unwrap();
+        view.write().unwrap().push(8080);
+        assert_eq!(*view.read().unwrap(), [80, 443, 8080]);
+        assert_eq!(owner.read().unwrap().ports, [80, 443, 8080]);
+        assert_eq!(format!("{view:?}"), "ProtectedView { id: Some(0), .. }");
+        assert!(matches!(
+            view.try_write(),
+            Ok(ports) if ports.len() == 3
+        ));
+    }
+
+    #[test]
+    fn views_are_controlled_like_users() {
+        let owner = config();
+        let view = owner
+            .create_read_only_view(0, |config| config.name.as_str())
+            .unwrap();
+        assert_eq!(&*view.read().unwrap(), "server");
+        assert!(matches!(
+            owner.create_read_only_view(0, |config| &config.ports),
+            Err(AccessError::UserExists { id: 0 })
+        ));
+        owner.remove_user(0).unwrap();
+        assert!(matches!(view.read(), Err(AccessError::Revoked { id: 0 })));
+    }
+
+    #[test]
+    fn weak_users_keep_their_access() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let weak = owner.downgrade_user(user).unwrap();
+        assert_eq!(format!("{weak:?}"), "WeakProtected { id: 0, .. }");
+        weak.write(|x| *x += 1).unwrap();
+        assert_eq!(weak.read(|x| *x), Ok(43));
+        drop(weak.upgrade().unwrap());
+        assert_eq!(weak.read(|x| *x), Ok(43));
+        assert!(matches!(
+            owner.create_user(0, Permissions::ReadWrite),
+            Err(AccessError::UserExists { id: 0 })
+        ));
+        drop(weak);
+        assert!(owner.create_user(0, Permissions::ReadWrite).is_ok());
+    }
+
+    #[test]
+    fn weak_users_do_not_keep_value_alive() {
+        let dropped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let owner = Protected::new(DropFlag(dropped.clone()));
+        let user = owner.create_read_only_user(0).unwrap();
+        let weak = owner.downgrade_user(user).unwrap();
+        assert!(weak.read(|_| ()).is_ok());
+        drop(owner);
+        assert!(dropped.load(Ordering::SeqCst));
+        assert!(matches!(
+            weak.read(|_| ()),
+            Err(AccessError::Destroyed { id: Some(0) })
+        ));
+    }
+
+    #[test]
+    fn weak_users_can_be_revoked() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let sub_user = user.delegate(1, Permissions::ReadOnly).unwrap();
+        let weak = owner.downgrade_user(user).unwrap();
+        assert_eq!(*sub_user.read().unwrap(), 42);
+        owner.remove_user(0).unwrap();
+        assert!(matches!(
+            weak.upgrade(),
+            Err(AccessError::Revoked { id: 0 })
+        ));
+        assert!(matches!(
+            sub_user.read(),
+            Err(AccessError::Revoked { id: 1 })
+        ));
+    }
+
+    #[test]
+    fn users_can_be_cloned_while_holding_a_guard() {
+        let owner = Protected::new(0);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let mut guard = user.write().unwrap();
+        let clone = user.clone();
+        let weak = owner.downgrade_user(user.clone()).unwrap();
+        drop(clone);
+        *guard += 1;
+        drop(guard);
+        assert_eq!(owner.handle_count(0).unwrap(), 2);
+        drop(user);
+        assert_eq!(weak.read(|x| *x), Ok(1));
+    }
+
+    #[test]
+    fn users_are_only_downgraded_by_their_owner() {
+        let owner = Protected::new(42);
+        let other = Protected::new(42);
+        owner.set_audit_log_capacity(8);
+        let user = other.create_user(0, Permissions::ReadWrite).unwrap();
+        let user = owner.downgrade_user(user).unwrap_err();
+        assert_eq!(*user.read().unwrap(), 42);
+        assert_eq!(other.handle_count(0).unwrap(), 1);
+        assert!(owner.audit_log().is_empty());
+    }
+
+    #[test]
+    fn user_clones_share_their_access_key() {
+        let owner = Protected::new(0);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let weak = owner.downgrade_user(user.clone()).unwrap();
+        assert_eq!(owner.handle_count(0).unwrap(), 2);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                let user = user.clone();
+                s.spawn(move || *user.write().unwrap() += 1);
+            }
+        });
+        assert_eq!(*owner.read().unwrap(), 4);
+        assert_eq!(owner.handle_count(0).unwrap(), 2);
+
+        drop(user);
+        assert_eq!(owner.handle_count(0).unwrap(), 1);
+        assert_eq!(weak.read(|x| *x), Ok(4));
+        drop(weak);
+        assert_eq!(owner.handle_count(0).unwrap(), 0);
+        assert!(owner.create_user(0, Permissions::ReadWrite).is_ok());
+    }
+
+    #[test]
+    fn revoked_user_clones_stay_revoked() {
+        let owner = Protected::new(42);
+        let user = owner.create_read_only_user(0).unwrap();
+        let clone = user.clone();
+        owner.remove_user(0).unwrap();
+        let user = owner.create_read_only_user(0).unwrap();
+        drop(clone.clone());
+        drop(clone);
+        assert_eq!(owner.handle_count(0).unwrap(), 1);
+        assert_eq!(*user.read().unwrap(), 42);
+    }
+
+    #[test]
+    fn suspended_users_regain_access_once_resumed() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        let sub_user = user.delegate(1, Permissions::ReadOnly).unwrap();
+        assert_eq!(owner.suspend(0), Ok(true));
+        assert_eq!(user.read().err(), Some(AccessError::Suspended { id: 0 }));
+        assert_eq!(user.write().err(), Some(AccessError::Suspended { id: 0 }));
+        assert_eq!(
+            sub_user.read().err(),
+            Some(AccessError::Suspended { id: 1 })
+        );
+        assert_eq!(owner.resume(0), Ok(true));
+        assert_eq!(*user.read().unwrap(), 42);
+        assert_eq!(*sub_user.read().unwrap(), 42);
+        assert_eq!(owner.suspend(2), Ok(false));
+    }
+
+    #[test]
+    fn suspend_all_applies_to_every_user() {
+        let owner = Protected::new(42);
+        let user = owner.create_user(0, Permissions::ReadWrite).unwrap();
+        owner.suspend(0).unwrap();
+        owner.suspend_all().unwrap();
+        let other = owner.create_read_only_user(1).unwrap();
+        assert_eq!(other.read().err(), Some(AccessError::Suspended { id: 1 }));
+        assert_eq!(*owner.read().unwrap(), 42);
+
+        owner.resume_all().unwrap();
+        assert_eq!(*other.read().unwrap(), 42);
+        assert_eq!(user.read().err(), Some(AccessError::Suspended { id: 0 }));
+        assert_eq!(
+            AccessError::Suspended { id: 0 }.to_string(),
+            "user 0 has been suspended"
+        );
+    }
+
+    /// Tests of creating, removing and recreating users, of permissions, and of
+    /// the audit log and stats, with users identified by strings instead of `u32`.
+    mod string_ids {
+        use super::*;
+
+        fn owner() -> Protected<i32, Owner, String> {
+            Protected::with_ids(42)
+        }
+
+        #[test]
+        fn user_can_read_and_write() {
+            let owner = owner();
+            let user = owner
+                .create_user("alice".to_string(), Permissions::ReadWrite)
+                .unwrap();
+            *user.write().unwrap() = 43;
+            assert_eq!(*user.read().unwrap(), 43);
+        }
+
+        #[test]
+        fn owner_cannot_create_duplicated_users() {
+            let owner = owner();
+            let _alice = owner.create_read_only_user("alice".to_string()).unwrap();
+            let error = owner
+                .create_read_only_user("alice".to_string())
+                .err()
+                .unwrap();
+            assert_eq!(
+                error,
+                AccessError::UserExists {
+                    id: "alice".to_string()
+                }
+            );
+            assert_eq!(error.to_string(), "user alice already exists");
+            assert!(owner.create_read_only_user("bob".to_string()).is_ok());
+        }
+
+        #[test]
+        fn removed_user_cannot_access() {
+            let owner = owner();
+            let user = owner
+                .create_user("alice".to_string(), Permissions::ReadWrite)
+                .unwrap();
+            owner.remove_user("alice".to_string()).unwrap();
+            assert_eq!(
+                user.read().err().and_then(|error| error.id()),
+                Some("alice".to_string())
+            );
+        }
+
+        #[test]
+        fn recreated_user_does_not_restore_stale_handle() {
+            let owner = owner();
+            let stale = owner.create_read_only_user("alice".to_string()).unwrap();
+            owner.remove_user("alice".to_string()).unwrap();
+            let fresh = owner.create_read_only_user("alice".to_string()).unwrap();
+            assert!(stale.read().is_err());
+            drop(stale);
+            assert!(fresh.read().is_ok());
+        }
+
+        #[test]
+        fn permissions_are_enforced() {
+            let owner = owner();
+            let user = owner
+                .create_user("alice".to_string(), Permissions::WriteOnly)
+                .unwrap();
+            assert!(user.write().is_ok());
+            assert_eq!(
+                user.read().err(),
+                Some(AccessError::PermissionDenied {
+                    id: "alice".to_string(),
+                    permissions: Permissions::WriteOnly,
+                    operation: Operation::Read,
+                })
+            );
+        }
+
+        #[test]
+        fn audit_log_reports_string_ids() {
+            let owner = owner();
+            owner.set_audit_log_capacity(16);
+            let user = owner.create_read_only_user("alice".to_string()).unwrap();
+            drop(user);
+            let kinds: Vec<_> = owner
+                .audit_log()
+                .into_iter()
+                .map(|event| event.kind)
+                .collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    AuditEventKind::UserCreated {
+                        id: "alice".to_string(),
+                        permissions: Permissions::ReadOnly,
+                    },
+                    AuditEventKind::UserDropped {
+                        id: "alice".to_string()
+                    },
+                ]
+            );
+        }
+
+        #[cfg(feature = "stats")]
+        #[test]
+        fn stats_are_keyed_by_string_ids() {
+            let owner = owner();
+            let user = owner.create_read_only_user("alice".to_string()).unwrap();
+            drop(user.read().unwrap());
+            assert_eq!(owner.stats().users["alice"].reads, 1);
+        }
+    }
+
+    /// Stress tests checking that revocation and lock acquisition never interleave.
+    ///
+    /// Workers flag a violation if they hold a guard while `revoked` is set, which
+    /// is only ever set after `remove_user` has returned. Since `remove_user` cannot
+    /// return while a guard is held, such a guard must have been issued afterwards.
+    mod stress {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::thread;
+
+        use super::*;
+
+        const ROUNDS: usize = 200;
+        const WORKERS: u32 = 4;
+
+        fn revoke_while_accessing(access: impl Fn(&Protected<u64, User>, &AtomicBool) + Sync) {
+            for _ in 0..ROUNDS {
+                let owner = Protected::new(0u64);
+                let users: Vec<_> = (0..WORKERS)
+                    .map(|id| owner.create_user(id, Permissions::ReadWrite).unwrap())
+                    .collect();
+                let revoked: Vec<_> = (0..WORKERS).map(|_| AtomicBool::new(false)).collect();
+
+                thread::scope(|scope| {
+                    for (user, revoked) in users.iter().zip(&revoked) {
+                        let access = &access;
+                        scope.spawn(move || access(user, revoked));
+                    }
+                    for (id, revoked) in (0..WORKERS).zip(&revoked) {
+                        thread::yield_now();
+                        owner.remove_user(id).unwrap();
+                        revoked.store(true, Ordering::SeqCst);
+                    }
+                });
+            }
+        }
+
+        #[test]
+        fn no_read_guard_is_issued_after_remove_user() {
+            revoke_while_accessing(|user, revoked| {
+                while let Ok(x) = user.read() {
+                    assert!(!revoked.load(Ordering::SeqCst), "read after revocation");
+                    drop(x);
+                }
+                assert!(user.read().is_err());
+            });
+        }
+
+        #[test]
+        fn no_write_guard_is_issued_after_remove_user() {
+            revoke_while_accessing(|user, revoked| {
+                while let Ok(mut x) = user.write() {
+                    assert!(!revoked.load(Ordering::SeqCst), "write after revocation");
+                    *x += 1;
+                }
+                assert!(user.write().is_err());
+            });
+        }
+
+        #[test]
+        fn no_guard_is_issued_after_remove_user_with_mixed_access() {
+            revoke_while_accessing(|user, revoked| loop {
+                if let Ok(x) = user.try_read() {
+                    assert!(!revoked.load(Ordering::SeqCst), "try_read after revocation");
+                    drop(x);
+                }
+                match user.write_timeout(Duration::from_millis(1)) {
+                    Ok(mut x) => {
+                        assert!(!revoked.load(Ordering::SeqCst), "write after revocation");
+                        *x += 1;
+                    }
+                    Err(AccessError::Revoked { .. }) => break,
+                    Err(_) => {}
+                }
+            });
+        }
     }
 }
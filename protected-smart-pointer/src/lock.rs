@@ -0,0 +1,308 @@
+use std::cell::UnsafeCell;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{PoisonError, TryLockError, TryLockResult};
+use std::thread;
+use std::time::Instant;
+
+use crate::notify::ReleaseNotifier;
+
+/// Set in the state of a [`Lock`] while it is held for writing.
+const WRITER: usize = 1;
+/// Set in the state of a [`Lock`] while it is held for upgradable reading.
+const UPGRADABLE: usize = 2;
+/// Added to the state of a [`Lock`] for every reader holding it, including
+/// the upgradable one.
+const READER: usize = 4;
+
+/// Reader-writer lock guarding the inner state of `Protected<T>`.
+///
+/// Unlike `std::sync::RwLock`, this lock never blocks the current thread by
+/// itself. Waiters retry every time the [`ReleaseNotifier`] they are given is
+/// notified, which every guard to `T` does once dropped. Its guards are thus
+/// not tied to the thread that acquired them, and are `Send` whenever `T` is
+/// `Send` and `Sync`, so that they can be held across `.await` points.
+///
+/// Readers wait while writers are waiting, so that writers cannot be starved.
+/// Every atomic operation is sequentially consistent, so that it pairs with the
+/// fence in [`ReleaseNotifier::notify`].
+pub(crate) struct Lock<T> {
+    state: AtomicUsize,
+    /// Number of threads waiting to lock for writing, or to upgrade.
+    waiting_writers: AtomicUsize,
+    poisoned: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: like `std::sync::RwLock`, this lock hands out mutable references to
+// `T` to a single thread at a time, or shared references to several threads.
+unsafe impl<T: Send> Send for Lock<T> {}
+unsafe impl<T: Send + Sync> Sync for Lock<T> {}
+
+/// Shared read access to the value guarded by a [`Lock`].
+pub(crate) struct ReadGuard<'a, T> {
+    lock: &'a Lock<T>,
+}
+
+/// Shared read access to the value guarded by a [`Lock`], which can be upgraded
+/// to exclusive write access.
+pub(crate) struct UpgradableReadGuard<'a, T> {
+    lock: &'a Lock<T>,
+}
+
+/// Exclusive write access to the value guarded by a [`Lock`].
+pub(crate) struct WriteGuard<'a, T> {
+    lock: &'a Lock<T>,
+    /// Whether the thread was already panicking when the lock was acquired,
+    /// in which case the lock is not poisoned once this guard is dropped.
+    panicking: bool,
+}
+
+impl<T> Lock<T> {
+    pub(crate) fn new(value: T) -> Lock<T> {
+        Lock {
+            state: AtomicUsize::new(0),
+            waiting_writers: AtomicUsize::new(0),
+            poisoned: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Locks for reading, waiting for `released` to be notified between attempts
+    /// until `deadline`, if any.
+    ///
+    /// Returns [`TryLockError::WouldBlock`] once `deadline` has been reached.
+    pub(crate) fn read(
+        &self,
+        released: &ReleaseNotifier,
+        deadline: Option<Instant>,
+    ) -> TryLockResult<ReadGuard<'_, T>> {
+        wait(released, deadline, || self.try_read())
+    }
+
+    /// Locks for upgradable reading, like [`read`](Lock::read).
+    pub(crate) fn upgradable_read(
+        &self,
+        released: &ReleaseNotifier,
+        deadline: Option<Instant>,
+    ) -> TryLockResult<UpgradableReadGuard<'_, T>> {
+        wait(released, deadline, || self.try_upgradable_read())
+    }
+
+    /// Locks for writing, like [`read`](Lock::read).
+    pub(crate) fn write(
+        &self,
+        released: &ReleaseNotifier,
+        deadline: Option<Instant>,
+    ) -> TryLockResult<WriteGuard<'_, T>> {
+        match self.try_write() {
+            Err(TryLockError::WouldBlock)
+                if deadline.is_none_or(|deadline| Instant::now() < deadline) => {}
+            result => return result,
+        }
+
+        self.start_waiting_to_write();
+        let result = wait(released, deadline, || self.try_write());
+        self.stop_waiting_to_write();
+        if result.is_err() {
+            // Readers may have been waiting for this writer only.
+            released.notify();
+        }
+        result
+    }
+
+    /// Counts a writer waiting for the lock, so that readers wait for it too
+    /// until [`stop_waiting_to_write`](Lock::stop_waiting_to_write) is called.
+    pub(crate) fn start_waiting_to_write(&self) {
+        self.waiting_writers.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Stops counting a writer counted by
+    /// [`start_waiting_to_write`](Lock::start_waiting_to_write).
+    ///
+    /// Unless the writer has acquired the lock, readers may have been waiting
+    /// for it only, so the caller has to notify them.
+    pub(crate) fn stop_waiting_to_write(&self) {
+        self.waiting_writers.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Attempts to lock for reading, which fails while a writer holds the lock
+    /// or waits for it.
+    pub(crate) fn try_read(&self) -> TryLockResult<ReadGuard<'_, T>> {
+        if !self.try_share(READER, WRITER) {
+            return Err(TryLockError::WouldBlock);
+        }
+        self.check_poison(ReadGuard { lock: self })
+    }
+
+    /// Attempts to lock for upgradable reading, which also fails while another
+    /// upgradable reader holds the lock.
+    pub(crate) fn try_upgradable_read(&self) -> TryLockResult<UpgradableReadGuard<'_, T>> {
+        if !self.try_share(READER | UPGRADABLE, WRITER | UPGRADABLE) {
+            return Err(TryLockError::WouldBlock);
+        }
+        self.check_poison(UpgradableReadGuard { lock: self })
+    }
+
+    /// Attempts to lock for writing, which fails while anyone holds the lock.
+    pub(crate) fn try_write(&self) -> TryLockResult<WriteGuard<'_, T>> {
+        if self
+            .state
+            .compare_exchange(0, WRITER, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(TryLockError::WouldBlock);
+        }
+        self.check_poison(WriteGuard::new(self))
+    }
+
+    /// Adds `reader` to the state, unless any of the `excluded` bits is set or
+    /// a writer is waiting.
+    fn try_share(&self, reader: usize, excluded: usize) -> bool {
+        let mut state = self.state.load(Ordering::SeqCst);
+        loop {
+            if state & excluded != 0 || self.waiting_writers.load(Ordering::SeqCst) > 0 {
+                return false;
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state + reader,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Checks if a thread panicked while holding a write guard to this lock.
+    pub(crate) fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::SeqCst)
+    }
+
+    fn check_poison<G>(&self, guard: G) -> TryLockResult<G> {
+        if self.is_poisoned() {
+            Err(TryLockError::Poisoned(PoisonError::new(guard)))
+        } else {
+            Ok(guard)
+        }
+    }
+}
+
+/// Calls `try_lock` until it stops returning [`TryLockError::WouldBlock`],
+/// waiting for `released` to be notified between attempts until `deadline`.
+fn wait<G>(
+    released: &ReleaseNotifier,
+    deadline: Option<Instant>,
+    try_lock: impl Fn() -> TryLockResult<G>,
+) -> TryLockResult<G> {
+    released
+        .wait_until(deadline, || match try_lock() {
+            Err(TryLockError::WouldBlock) => None,
+            result => Some(result),
+        })
+        .unwrap_or(Err(TryLockError::WouldBlock))
+}
+
+impl<'a, T> UpgradableReadGuard<'a, T> {
+    /// Atomically converts this guard to exclusive write access, waiting for
+    /// `released` to be notified between attempts until the other readers have
+    /// released the lock.
+    pub(crate) fn upgrade(guard: Self, released: &ReleaseNotifier) -> WriteGuard<'a, T> {
+        let lock = ManuallyDrop::new(guard).lock;
+        lock.waiting_writers.fetch_add(1, Ordering::SeqCst);
+        released.wait_until(None, || {
+            lock.state
+                .compare_exchange(
+                    READER | UPGRADABLE,
+                    WRITER,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .ok()
+        });
+        lock.waiting_writers.fetch_sub(1, Ordering::SeqCst);
+        WriteGuard::new(lock)
+    }
+}
+
+impl<'a, T> WriteGuard<'a, T> {
+    fn new(lock: &'a Lock<T>) -> WriteGuard<'a, T> {
+        WriteGuard {
+            lock,
+            panicking: thread::panicking(),
+        }
+    }
+
+    /// Atomically converts this guard to shared read access.
+    pub(crate) fn downgrade(guard: Self) -> ReadGuard<'a, T> {
+        guard.poison();
+        let lock = ManuallyDrop::new(guard).lock;
+        // No reader can get in while the lock is held for writing, so nobody else
+        // changes the state meanwhile.
+        lock.state.store(READER, Ordering::SeqCst);
+        ReadGuard { lock }
+    }
+
+    /// Poisons the lock if the thread started panicking while holding this guard.
+    fn poison(&self) {
+        if !self.panicking && thread::panicking() {
+            self.lock.poisoned.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+impl<T> Deref for ReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: no writer holds the lock for as long as this guard is held.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Deref for UpgradableReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: as for `ReadGuard`.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Deref for WriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: nobody else holds the lock for as long as this guard is held.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for WriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: as in `deref`, and `self` is borrowed mutably, so this is the
+        // only reference to `T` that is alive.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for ReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(READER, Ordering::SeqCst);
+    }
+}
+
+impl<T> Drop for UpgradableReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock
+            .state
+            .fetch_sub(READER | UPGRADABLE, Ordering::SeqCst);
+    }
+}
+
+impl<T> Drop for WriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.poison();
+        self.lock.state.fetch_sub(WRITER, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+use crate::{ProtectedReadGuard, ProtectedWriteGuard};
+
+/// RAII structure used to release the shared read access of a lock when dropped,
+/// which only gives access to a part of `T`.
+///
+/// This structure is returned by [`ProtectedReadGuard::map`] and
+/// [`ProtectedReadGuard::try_map`].
+#[must_use = "if unused the lock will immediately be released"]
+pub struct MappedProtectedReadGuard<'a, T, U: ?Sized, Id = u32> {
+    // Points into the `T` locked by `guard`, which stays where it is for as long
+    // as `guard` is held, even if the guard itself moves.
+    value: NonNull<U>,
+    // Whether `T` is a secret, which is found out before mapping, as `guard` must
+    // not be used to access `T` anymore while `value` is in use.
+    redacted: bool,
+    guard: ProtectedReadGuard<'a, T, Id>,
+}
+
+/// RAII structure used to release the exclusive write access of a lock when dropped,
+/// which only gives access to a part of `T`.
+///
+/// This structure is returned by [`ProtectedWriteGuard::map`] and
+/// [`ProtectedWriteGuard::try_map`].
+#[must_use = "if unused the lock will immediately be released"]
+pub struct MappedProtectedWriteGuard<'a, T, U: ?Sized, Id = u32> {
+    // Points into the `T` locked by `guard`, like in `MappedProtectedReadGuard`.
+    value: NonNull<U>,
+    // Whether `T` is a secret, like in `MappedProtectedReadGuard`.
+    redacted: bool,
+    guard: ProtectedWriteGuard<'a, T, Id>,
+    // Mutable references are invariant, so this guard must be invariant over `U` too.
+    _marker: PhantomData<&'a mut U>,
+}
+
+impl<'a, T, Id> ProtectedReadGuard<'a, T, Id> {
+    /// Makes a guard to a part of `T`, such as a field, keeping `T` locked.
+    ///
+    /// This is an associated function that needs to be used as
+    /// `ProtectedReadGuard::map(guard, ...)`, so as not to conflict with
+    /// methods of `T`.
+    pub fn map<U: ?Sized>(
+        orig: Self,
+        f: impl FnOnce(&T) -> &U,
+    ) -> MappedProtectedReadGuard<'a, T, U, Id> {
+        MappedProtectedReadGuard {
+            redacted: orig.guard.zeroize.is_some(),
+            value: NonNull::from(f(&*orig)),
+            guard: orig,
+        }
+    }
+
+    /// Makes a guard to a part of `T` that may not exist, such as an element,
+    /// keeping `T` locked.
+    ///
+    /// The original guard is handed back if `f` returns `None`. This is an
+    /// associated function, like [`ProtectedReadGuard::map`].
+    pub fn try_map<U: ?Sized>(
+        orig: Self,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> Result<MappedProtectedReadGuard<'a, T, U, Id>, Self> {
+        let redacted = orig.guard.zeroize.is_some();
+        match f(&*orig).map(NonNull::from) {
+            Some(value) => Ok(MappedProtectedReadGuard {
+                value,
+                redacted,
+                guard: orig,
+            }),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<'a, T, Id> ProtectedWriteGuard<'a, T, Id> {
+    /// Makes a guard to a part of `T`, such as a field, keeping `T` locked.
+    ///
+    /// This is an associated function that needs to be used as
+    /// `ProtectedWriteGuard::map(guard, ...)`, so as not to conflict with
+    /// methods of `T`.
+    pub fn map<U: ?Sized>(
+        mut orig: Self,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedProtectedWriteGuard<'a, T, U, Id> {
+        MappedProtectedWriteGuard {
+            redacted: orig.guard.zeroize.is_some(),
+            value: NonNull::from(f(&mut *orig)),
+            guard: orig,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes a guard to a part of `T` that may not exist, such as an element,
+    /// keeping `T` locked.
+    ///
+    /// The original guard is handed back if `f` returns `None`. This is an
+    /// associated function, like [`ProtectedWriteGuard::map`].
+    pub fn try_map<U: ?Sized>(
+        mut orig: Self,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Result<MappedProtectedWriteGuard<'a, T, U, Id>, Self> {
+        let redacted = orig.guard.zeroize.is_some();
+        match f(&mut *orig).map(NonNull::from) {
+            Some(value) => Ok(MappedProtectedWriteGuard {
+                value,
+                redacted,
+                guard: orig,
+                _marker: PhantomData,
+            }),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<'a, T, U: ?Sized, Id> MappedProtectedReadGuard<'a, T, U, Id> {
+    /// Makes a guard to a part of the part of `T` this guard gives access to.
+    ///
+    /// This is an associated function, like [`ProtectedReadGuard::map`].
+    pub fn map<V: ?Sized>(
+        orig: Self,
+        f: impl FnOnce(&U) -> &V,
+    ) -> MappedProtectedReadGuard<'a, T, V, Id> {
+        MappedProtectedReadGuard {
+            value: NonNull::from(f(&*orig)),
+            redacted: orig.redacted,
+            guard: orig.guard,
+        }
+    }
+
+    /// Makes a guard to a part of the part of `T` this guard gives access to,
+    /// which may not exist.
+    ///
+    /// This is an associated function, like [`ProtectedReadGuard::try_map`].
+    pub fn try_map<V: ?Sized>(
+        orig: Self,
+        f: impl FnOnce(&U) -> Option<&V>,
+    ) -> Result<MappedProtectedReadGuard<'a, T, V, Id>, Self> {
+        match f(&*orig).map(NonNull::from) {
+            Some(value) => Ok(MappedProtectedReadGuard {
+                value,
+                redacted: orig.redacted,
+                guard: orig.guard,
+            }),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<'a, T, U: ?Sized, Id> MappedProtectedWriteGuard<'a, T, U, Id> {
+    /// Makes a guard to a part of the part of `T` this guard gives access to.
+    ///
+    /// This is an associated function, like [`ProtectedWriteGuard::map`].
+    pub fn map<V: ?Sized>(
+        mut orig: Self,
+        f: impl FnOnce(&mut U) -> &mut V,
+    ) -> MappedProtectedWriteGuard<'a, T, V, Id> {
+        MappedProtectedWriteGuard {
+            value: NonNull::from(f(&mut *orig)),
+            redacted: orig.redacted,
+            guard: orig.guard,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes a guard to a part of the part of `T` this guard gives access to,
+    /// which may not exist.
+    ///
+    /// This is an associated function, like [`ProtectedWriteGuard::try_map`].
+    pub fn try_map<V: ?Sized>(
+        mut orig: Self,
+        f: impl FnOnce(&mut U) -> Option<&mut V>,
+    ) -> Result<MappedProtectedWriteGuard<'a, T, V, Id>, Self> {
+        match f(&mut *orig).map(NonNull::from) {
+            Some(value) => Ok(MappedProtectedWriteGuard {
+                value,
+                redacted: orig.redacted,
+                guard: orig.guard,
+                _marker: PhantomData,
+            }),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<T, U: ?Sized, Id> Deref for MappedProtectedReadGuard<'_, T, U, Id> {
+    type Target = U;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: `value` was derived from a shared reference to the `T` locked by
+        // `self.guard`, which keeps `T` locked for reading as long as `self` lives.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T, U: ?Sized, Id> Deref for MappedProtectedWriteGuard<'_, T, U, Id> {
+    type Target = U;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: `value` was derived from a mutable reference to the `T` locked by
+        // `self.guard`, which keeps `T` locked for writing as long as `self` lives.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T, U: ?Sized, Id> DerefMut for MappedProtectedWriteGuard<'_, T, U, Id> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: as in `deref`, and `self` is borrowed mutably, so this is the
+        // only reference to `U` that is alive.
+        unsafe { self.value.as_mut() }
+    }
+}
+
+impl<T, U: ?Sized + fmt::Debug, Id> fmt::Debug for MappedProtectedReadGuard<'_, T, U, Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.redacted {
+            return f.write_str("<redacted>");
+        }
+        (**self).fmt(f)
+    }
+}
+
+impl<T, U: ?Sized + fmt::Debug, Id> fmt::Debug for MappedProtectedWriteGuard<'_, T, U, Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.redacted {
+            return f.write_str("<redacted>");
+        }
+        (**self).fmt(f)
+    }
+}
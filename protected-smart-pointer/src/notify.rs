@@ -0,0 +1,210 @@
+use std::mem;
+use std::sync::atomic::{fence, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::task::Waker;
+use std::time::Instant;
+
+use crate::stats::{Counters, Stopwatch};
+
+/// Wakes up threads waiting for the lock guarding `T` to be released.
+///
+/// The lock guarding `T` never blocks by itself, so acquiring it, with or without
+/// a timeout, and asynchronously, is built on top of its `try_read`, `try_write`
+/// and `try_upgradable_read` methods: waiters retry every time a guard is released.
+pub(crate) struct ReleaseNotifier {
+    /// Number of blocked threads plus number of registered wakers.
+    waiters: AtomicUsize,
+    wakers: Mutex<Vec<Waker>>,
+    condvar: Condvar,
+    /// Number of exclusive guards released so far, which is how waiters tell
+    /// whether `T` or its access keys may have changed since they last looked.
+    exclusive_releases: AtomicU64,
+}
+
+impl ReleaseNotifier {
+    pub(crate) fn new() -> ReleaseNotifier {
+        ReleaseNotifier {
+            waiters: AtomicUsize::new(0),
+            wakers: Mutex::new(Vec::new()),
+            condvar: Condvar::new(),
+            exclusive_releases: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the number of exclusive guards released so far.
+    pub(crate) fn exclusive_releases(&self) -> u64 {
+        self.exclusive_releases.load(Ordering::SeqCst)
+    }
+
+    /// Wakes up every waiting thread and task.
+    ///
+    /// This is cheap when nobody is waiting, so it is called every time
+    /// a guard is dropped.
+    pub(crate) fn notify(&self) {
+        // Pairs with the increments in `wait_until` and `register`, so that either
+        // the waiter sees the lock released in its next attempt, or we see the waiter.
+        fence(Ordering::SeqCst);
+        if self.waiters.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+
+        let wakers = {
+            let mut wakers = self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+            self.condvar.notify_all();
+            self.waiters.fetch_sub(wakers.len(), Ordering::SeqCst);
+            mem::take(&mut *wakers)
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Arranges for `waker` to be woken up the next time a guard is released.
+    ///
+    /// Callers must attempt to acquire the lock once more after registering,
+    /// as the lock may have been released before the waker was registered.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn register(&self, waker: &Waker) {
+        let mut wakers = self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+        if !wakers.iter().any(|registered| registered.will_wake(waker)) {
+            self.waiters.fetch_add(1, Ordering::SeqCst);
+            wakers.push(waker.clone());
+        }
+    }
+
+    /// Calls `attempt` until it returns `Some`, waiting for a notification
+    /// between attempts.
+    ///
+    /// Returns `None` if `deadline` is reached before `attempt` succeeds.
+    /// Without a deadline, this function waits for as long as it takes.
+    pub(crate) fn wait_until<R>(
+        &self,
+        deadline: Option<Instant>,
+        mut attempt: impl FnMut() -> Option<R>,
+    ) -> Option<R> {
+        if let Some(result) = attempt() {
+            return Some(result);
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return None;
+        }
+
+        let mut lock = self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        let result = loop {
+            if let Some(result) = attempt() {
+                break Some(result);
+            }
+
+            lock = match deadline {
+                None => self
+                    .condvar
+                    .wait(lock)
+                    .unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break None;
+                    }
+                    self.condvar
+                        .wait_timeout(lock, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+            };
+        };
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+}
+
+/// Notifies a [`ReleaseNotifier`] when dropped.
+///
+/// Guards hold one of these as their last field, so that the notification
+/// is sent right after the lock itself has been released. Beforehand, guards
+/// obtained by a user stop counting towards the guards held by that user, and
+/// the time during which guards were held is accounted for.
+pub(crate) struct Release<'a> {
+    notifier: &'a ReleaseNotifier,
+    held_guards: Option<&'a AtomicUsize>,
+    holding: Option<(&'a Counters, Stopwatch)>,
+    /// Whether the guard holding this `Release` has exclusive access.
+    exclusive: bool,
+    /// The version of `T` to bump once the guard holding this `Release` is dropped.
+    version: Option<&'a AtomicU64>,
+}
+
+impl<'a> Release<'a> {
+    pub(crate) fn new(notifier: &'a ReleaseNotifier) -> Release<'a> {
+        Release {
+            notifier,
+            held_guards: None,
+            holding: None,
+            exclusive: false,
+            version: None,
+        }
+    }
+
+    /// Like [`Release::new`], for guards with exclusive access.
+    pub(crate) fn exclusive(notifier: &'a ReleaseNotifier) -> Release<'a> {
+        let mut release = Release::new(notifier);
+        release.exclusive = true;
+        release
+    }
+
+    /// Counts the guard holding this `Release` in `held_guards` until it is dropped,
+    /// unless it is counted already.
+    pub(crate) fn track(&mut self, held_guards: &'a AtomicUsize) {
+        if self.held_guards.is_none() {
+            held_guards.fetch_add(1, Ordering::SeqCst);
+            self.held_guards = Some(held_guards);
+        }
+    }
+
+    /// Adds the time from now until this `Release` is dropped to `counters`.
+    pub(crate) fn measure(&mut self, counters: &'a Counters) {
+        self.holding = Some((counters, Stopwatch::start()));
+    }
+
+    /// Bumps `version` once this `Release` is dropped, as `T` may have been written.
+    pub(crate) fn bump(&mut self, version: &'a AtomicU64) {
+        self.version = Some(version);
+    }
+
+    /// Accounts for the guard holding this `Release` gaining exclusive access.
+    pub(crate) fn upgrade(&mut self) {
+        self.exclusive = true;
+    }
+
+    /// Accounts for the guard holding this `Release` giving up exclusive access
+    /// without being dropped.
+    pub(crate) fn downgrade(&mut self) {
+        self.release_exclusive();
+        self.notifier.notify();
+    }
+
+    fn release_exclusive(&mut self) {
+        if let Some(version) = self.version.take() {
+            version.fetch_add(1, Ordering::SeqCst);
+        }
+        if self.exclusive {
+            self.exclusive = false;
+            self.notifier
+                .exclusive_releases
+                .fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for Release<'_> {
+    fn drop(&mut self) {
+        if let Some((counters, holding)) = self.holding.take() {
+            counters.released(holding);
+        }
+        if let Some(held_guards) = self.held_guards.take() {
+            held_guards.fetch_sub(1, Ordering::SeqCst);
+        }
+        self.release_exclusive();
+        self.notifier.notify();
+    }
+}
@@ -0,0 +1,123 @@
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError, TryLockError};
+use std::thread;
+
+/// Copy of the value guarded by the lock of `Protected<T>`, which can be read
+/// without acquiring that lock.
+///
+/// Readers never wait: they announce themselves in one of two reader counts,
+/// and clone the copy. The copy is replaced by swapping it for another, after
+/// which the previous copy is only dropped once no reader may still be cloning
+/// it. Every reader count is seen at zero at least once after the swap, and
+/// readers switch from one count to the other on every replacement, so that
+/// this does not take longer than a few clones.
+///
+/// Every atomic operation is sequentially consistent: a reader that is not
+/// seen by the thread replacing the copy loads the pointer after the swap.
+pub(crate) struct Slot<T> {
+    current: AtomicPtr<Published<T>>,
+    /// Number of readers that may be cloning the copy, in each count.
+    readers: [AtomicUsize; 2],
+    /// Selects the count readers announce themselves in.
+    epoch: AtomicUsize,
+    /// Held while replacing the copy.
+    replacing: Mutex<()>,
+    _marker: PhantomData<*const T>,
+}
+
+// SAFETY: readers get clones of `T`, and the copy is dropped by whichever
+// thread replaces it, as if it were behind an `Arc`.
+unsafe impl<T: Send + Sync> Send for Slot<T> {}
+unsafe impl<T: Send + Sync> Sync for Slot<T> {}
+
+/// A copy of the value guarded by the lock, along with the version of that value.
+struct Published<T> {
+    value: T,
+    version: u64,
+}
+
+/// Announces a reader in one of the reader counts of a [`Slot`] until dropped.
+struct Reading<'a>(&'a AtomicUsize);
+
+impl Drop for Reading<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<T> Slot<T> {
+    pub(crate) fn new() -> Slot<T> {
+        Slot {
+            current: AtomicPtr::new(ptr::null_mut()),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            epoch: AtomicUsize::new(0),
+            replacing: Mutex::new(()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Drops the copy, if any.
+    pub(crate) fn clear(&self) {
+        let _replacing = self
+            .replacing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.replace(ptr::null_mut());
+    }
+
+    /// Swaps the copy for `published`, and drops the previous copy once no
+    /// reader can be cloning it anymore.
+    ///
+    /// Must be called while holding `replacing`.
+    fn replace(&self, published: *mut Published<T>) {
+        let previous = self.current.swap(published, Ordering::SeqCst);
+        if previous.is_null() {
+            return;
+        }
+        for _ in 0..2 {
+            let epoch = self.epoch.fetch_add(1, Ordering::SeqCst) % 2;
+            while self.readers[epoch].load(Ordering::SeqCst) != 0 {
+                thread::yield_now();
+            }
+        }
+        // SAFETY: `previous` was leaked from a box by `store`, and no reader
+        // can still be using it.
+        drop(unsafe { Box::from_raw(previous) });
+    }
+}
+
+impl<T: Clone> Slot<T> {
+    /// Returns a clone of the copy, unless it is not a copy of the given version.
+    pub(crate) fn load(&self, version: u64) -> Option<T> {
+        let readers = &self.readers[self.epoch.load(Ordering::SeqCst) % 2];
+        readers.fetch_add(1, Ordering::SeqCst);
+        let _reading = Reading(readers);
+        let published = self.current.load(Ordering::SeqCst);
+        // SAFETY: the copy is not dropped while this reader is announced.
+        let published = unsafe { published.as_ref() }?;
+        (published.version == version).then(|| published.value.clone())
+    }
+
+    /// Replaces the copy with `value`, which is the given version of the value
+    /// guarded by the lock, unless another thread is replacing it already.
+    pub(crate) fn store(&self, value: T, version: u64) {
+        let _replacing = match self.replacing.try_lock() {
+            Ok(replacing) => replacing,
+            Err(TryLockError::Poisoned(error)) => error.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        self.replace(Box::into_raw(Box::new(Published { value, version })));
+    }
+}
+
+impl<T> Drop for Slot<T> {
+    fn drop(&mut self) {
+        let published = *self.current.get_mut();
+        if !published.is_null() {
+            // SAFETY: `published` was leaked from a box by `store`.
+            drop(unsafe { Box::from_raw(published) });
+        }
+    }
+}
@@ -0,0 +1,262 @@
+// Both implementations below expose the same crate-internal interface, so that
+// disabling the `stats` feature compiles counting down to nothing.
+
+#[cfg(feature = "stats")]
+pub use self::enabled::{AccessStats, ProtectedStats};
+#[cfg(feature = "stats")]
+pub(crate) use self::enabled::{Counters, Registry, SharedCounters, Stopwatch};
+
+#[cfg(not(feature = "stats"))]
+pub(crate) use self::disabled::{Counters, Registry, SharedCounters, Stopwatch};
+
+#[cfg(feature = "stats")]
+mod enabled {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex, PoisonError};
+    use std::time::{Duration, Instant};
+
+    use crate::{Operation, Owner, Protected, UserId};
+
+    /// How a single handle to `T` has used it.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct AccessStats {
+        /// Number of read guards handed out.
+        pub reads: u64,
+        /// Number of write guards handed out.
+        pub writes: u64,
+        /// Number of attempts that were denied access to `T`.
+        pub denials: u64,
+        /// Number of attempts that gave up on the lock guarding `T`, because they
+        /// timed out or would have blocked.
+        pub timeouts: u64,
+        /// Total time spent waiting for the lock guarding `T`, including the
+        /// attempts that failed.
+        pub wait_time: Duration,
+        /// Total time during which guards were held.
+        pub hold_time: Duration,
+    }
+
+    /// Snapshot of the statistics of a `Protected<T>`, returned by
+    /// [`Protected::stats`].
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ProtectedStats<Id: UserId = u32> {
+        /// How the owner has used `T`.
+        pub owner: AccessStats,
+        /// How each user has used `T`, by user ID.
+        ///
+        /// Users are only accounted for until their access is revoked or dropped,
+        /// so that users that are gone do not pile up. Handles sharing an access
+        /// key are accounted for together.
+        pub users: HashMap<Id, AccessStats>,
+    }
+
+    /// Counters updated by the handles to `T` that share an identity.
+    #[derive(Default)]
+    pub(crate) struct Counters {
+        reads: AtomicU64,
+        writes: AtomicU64,
+        denials: AtomicU64,
+        timeouts: AtomicU64,
+        wait_nanos: AtomicU64,
+        hold_nanos: AtomicU64,
+    }
+
+    impl Counters {
+        /// Counts a guard handed out after waiting since `waiting` was started.
+        pub(crate) fn granted(&self, operation: Operation, waiting: Stopwatch) {
+            let count = match operation {
+                Operation::Read => &self.reads,
+                Operation::Write => &self.writes,
+            };
+            count.fetch_add(1, Ordering::Relaxed);
+            self.wait_nanos
+                .fetch_add(waiting.elapsed_nanos(), Ordering::Relaxed);
+        }
+
+        /// Counts an attempt denied after waiting since `waiting` was started.
+        pub(crate) fn denied(&self, waiting: Stopwatch) {
+            self.denials.fetch_add(1, Ordering::Relaxed);
+            self.wait_nanos
+                .fetch_add(waiting.elapsed_nanos(), Ordering::Relaxed);
+        }
+
+        /// Counts an attempt that gave up on the lock after waiting since
+        /// `waiting` was started.
+        pub(crate) fn timed_out(&self, waiting: Stopwatch) {
+            self.timeouts.fetch_add(1, Ordering::Relaxed);
+            self.waited(waiting);
+        }
+
+        /// Counts the time spent waiting since `waiting` was started by an
+        /// attempt that failed for some other reason.
+        pub(crate) fn waited(&self, waiting: Stopwatch) {
+            self.wait_nanos
+                .fetch_add(waiting.elapsed_nanos(), Ordering::Relaxed);
+        }
+
+        /// Counts the time during which a guard acquired when `holding` was
+        /// started has been held.
+        pub(crate) fn released(&self, holding: Stopwatch) {
+            self.hold_nanos
+                .fetch_add(holding.elapsed_nanos(), Ordering::Relaxed);
+        }
+
+        fn snapshot(&self) -> AccessStats {
+            AccessStats {
+                reads: self.reads.load(Ordering::Relaxed),
+                writes: self.writes.load(Ordering::Relaxed),
+                denials: self.denials.load(Ordering::Relaxed),
+                timeouts: self.timeouts.load(Ordering::Relaxed),
+                wait_time: Duration::from_nanos(self.wait_nanos.load(Ordering::Relaxed)),
+                hold_time: Duration::from_nanos(self.hold_nanos.load(Ordering::Relaxed)),
+            }
+        }
+    }
+
+    /// Measures the time elapsed since it was started.
+    #[derive(Clone, Copy)]
+    pub(crate) struct Stopwatch(Instant);
+
+    impl Stopwatch {
+        pub(crate) fn start() -> Stopwatch {
+            Stopwatch(Instant::now())
+        }
+
+        fn elapsed_nanos(self) -> u64 {
+            u64::try_from(self.0.elapsed().as_nanos()).unwrap_or(u64::MAX)
+        }
+    }
+
+    /// Counters held by each handle to `T`, which are shared with its clones.
+    pub(crate) type SharedCounters = Arc<Counters>;
+
+    /// Counters of the owner and of every user holding an access key.
+    pub(crate) struct Registry<Id> {
+        owner: Arc<Counters>,
+        users: Mutex<HashMap<Id, Arc<Counters>>>,
+    }
+
+    impl<Id: UserId> Registry<Id> {
+        pub(crate) fn new() -> Registry<Id> {
+            Registry {
+                owner: Arc::default(),
+                users: Mutex::default(),
+            }
+        }
+
+        /// Returns the counters of the owner.
+        pub(crate) fn owner(&self) -> SharedCounters {
+            self.owner.clone()
+        }
+
+        /// Returns the counters shared by every handle to the user with the given ID.
+        pub(crate) fn user(&self, id: &Id) -> SharedCounters {
+            let mut users = self.users.lock().unwrap_or_else(PoisonError::into_inner);
+            users.entry(id.clone()).or_default().clone()
+        }
+
+        /// Forgets the counters of the users with the given IDs, whose access keys
+        /// have been removed.
+        pub(crate) fn remove<'a>(&self, ids: impl IntoIterator<Item = &'a Id>)
+        where
+            Id: 'a,
+        {
+            let mut users = self.users.lock().unwrap_or_else(PoisonError::into_inner);
+            for id in ids {
+                users.remove(id);
+            }
+        }
+
+        /// Forgets the counters of every user, once every access key has been removed.
+        pub(crate) fn clear(&self) {
+            let mut users = self.users.lock().unwrap_or_else(PoisonError::into_inner);
+            users.clear();
+        }
+    }
+
+    impl<T, Id: UserId> Protected<T, Owner, Id> {
+        /// Returns how the owner and each user have used `T` so far.
+        pub fn stats(&self) -> ProtectedStats<Id> {
+            let registry = &self.inner.stats;
+            let users = registry
+                .users
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            ProtectedStats {
+                owner: registry.owner.snapshot(),
+                users: users
+                    .iter()
+                    .map(|(id, counters)| (id.clone(), counters.snapshot()))
+                    .collect(),
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+mod disabled {
+    use std::marker::PhantomData;
+    use std::ops::Deref;
+
+    use crate::Operation;
+
+    pub(crate) struct Counters;
+
+    /// Counters held by each handle to `T`, which take no space, as every handle
+    /// shares the same counters, which count nothing.
+    #[derive(Clone)]
+    pub(crate) struct SharedCounters;
+
+    impl Deref for SharedCounters {
+        type Target = Counters;
+        fn deref(&self) -> &Counters {
+            &Counters
+        }
+    }
+
+    impl Counters {
+        pub(crate) fn granted(&self, _operation: Operation, _waiting: Stopwatch) {}
+
+        pub(crate) fn denied(&self, _waiting: Stopwatch) {}
+
+        pub(crate) fn timed_out(&self, _waiting: Stopwatch) {}
+
+        pub(crate) fn waited(&self, _waiting: Stopwatch) {}
+
+        pub(crate) fn released(&self, _holding: Stopwatch) {}
+    }
+
+    #[derive(Clone, Copy)]
+    pub(crate) struct Stopwatch;
+
+    impl Stopwatch {
+        pub(crate) fn start() -> Stopwatch {
+            Stopwatch
+        }
+    }
+
+    pub(crate) struct Registry<Id>(PhantomData<Id>);
+
+    impl<Id> Registry<Id> {
+        pub(crate) fn new() -> Registry<Id> {
+            Registry(PhantomData)
+        }
+
+        pub(crate) fn owner(&self) -> SharedCounters {
+            SharedCounters
+        }
+
+        pub(crate) fn user(&self, _id: &Id) -> SharedCounters {
+            SharedCounters
+        }
+
+        pub(crate) fn remove<'a>(&self, _ids: impl IntoIterator<Item = &'a Id>)
+        where
+            Id: 'a,
+        {
+        }
+
+        pub(crate) fn clear(&self) {}
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{AccessError, AuditEventKind, Owner, Protected, UserId};
+
+impl<T, Id: UserId> Protected<T, Owner, Id> {
+    /// Suspends the access of the user with a given ID, until it is resumed with
+    /// [`Protected::resume`].
+    ///
+    /// Unlike [`Protected::remove_user`], the handles of the user are not revoked,
+    /// and regain access to `T` once the user is resumed. Meanwhile, they are
+    /// denied access with [`AccessError::Suspended`], as are the users it has
+    /// delegated its access to. Guards obtained beforehand are not affected.
+    /// Returns `false` if there is no such user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn suspend(&self, id: Id) -> Result<bool, AccessError<Id>> {
+        self.set_suspended(id, true)
+    }
+
+    /// Resumes the access of the user with a given ID, after it was suspended with
+    /// [`Protected::suspend`].
+    ///
+    /// Returns `false` if there is no such user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn resume(&self, id: Id) -> Result<bool, AccessError<Id>> {
+        self.set_suspended(id, false)
+    }
+
+    /// Suspends the access of every user, including users created afterwards,
+    /// until it is resumed with [`Protected::resume_all`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn suspend_all(&self) -> Result<(), AccessError<Id>> {
+        self.set_all_suspended(true)
+    }
+
+    /// Resumes the access of every user, after it was suspended with
+    /// [`Protected::suspend_all`].
+    ///
+    /// Users suspended individually stay suspended.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn resume_all(&self) -> Result<(), AccessError<Id>> {
+        self.set_all_suspended(false)
+    }
+
+    fn set_suspended(&self, id: Id, suspended: bool) -> Result<bool, AccessError<Id>> {
+        let mut control = self.write_control()?;
+        let Some(access_key) = control.access_keys.get_mut(&id) else {
+            return Ok(false);
+        };
+        if access_key.suspended != suspended {
+            access_key.suspended = suspended;
+            self.inner.audit.record(|| {
+                if suspended {
+                    AuditEventKind::UserSuspended { id }
+                } else {
+                    AuditEventKind::UserResumed { id }
+                }
+            });
+        }
+        Ok(true)
+    }
+
+    fn set_all_suspended(&self, suspended: bool) -> Result<(), AccessError<Id>> {
+        let mut control = self.write_control()?;
+        if control.users_suspended != suspended {
+            control.users_suspended = suspended;
+            self.inner.audit.record(|| {
+                if suspended {
+                    AuditEventKind::UsersSuspended
+                } else {
+                    AuditEventKind::UsersResumed
+                }
+            });
+        }
+        Ok(())
+    }
+}
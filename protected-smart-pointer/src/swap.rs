@@ -0,0 +1,166 @@
+use std::mem;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::stats::Stopwatch;
+use crate::{AccessError, Operation, Owner, Protected, User, UserAccess, UserId, Wait};
+
+/// Snapshots of `T` are supported by storing `T` behind an [`Arc`], in a
+/// `Protected<Arc<T>>`, which is swapped for a new one on every update.
+///
+/// Taking a snapshot does not acquire the lock guarding the `Arc`: the current
+/// version is also kept apart from the lock, and is cloned from there once the
+/// access key of the user has been checked. Only the first snapshot taken after
+/// every update clones the `Arc` under the lock, to keep the new version apart.
+/// Publishing a new version still acquires the lock, and waits for it like
+/// [`Protected::write`] does, but only for as long as it takes to swap the `Arc`,
+/// so writers can prepare a new version of `T` while the lock is free.
+impl<T, Id: UserId> Protected<Arc<T>, Owner, Id> {
+    /// Returns the current version of `T`, which is not affected by later updates.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn snapshot(&self) -> Result<Arc<T>, AccessError<Id>> {
+        self.snapshot_checked()
+    }
+
+    /// Replaces the current version of `T` with `value`, and returns the new version.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn publish(&self, value: T) -> Result<Arc<T>, AccessError<Id>> {
+        self.publish_checked(Arc::new(value))
+    }
+
+    /// Replaces the current version of `T` with the one computed by `f` from it,
+    /// and returns the new version.
+    ///
+    /// `f` is called without holding the lock guarding `T`. If another version of `T`
+    /// has been published in the meantime, `f` is called again with that version.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn update(&self, f: impl FnMut(&T) -> T) -> Result<Arc<T>, AccessError<Id>> {
+        self.update_checked(f)
+    }
+}
+
+impl<T, A: UserAccess, Id: UserId> Protected<Arc<T>, A, Id> {
+    /// Returns the current version of `T`, which is not affected by later updates.
+    ///
+    /// Access is only checked when taking the snapshot, so the snapshot stays
+    /// usable even if this user is revoked afterwards.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`read`](Protected::read) if this user is denied access to `T`.
+    pub fn snapshot(&self) -> Result<Arc<T>, AccessError<Id>> {
+        self.snapshot_checked()
+    }
+}
+
+impl<T, Id: UserId> Protected<Arc<T>, User, Id> {
+    /// Replaces the current version of `T` with `value`, and returns the new version.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`write`](Protected::write) if this user is denied access to `T`.
+    pub fn publish(&self, value: T) -> Result<Arc<T>, AccessError<Id>> {
+        self.publish_checked(Arc::new(value))
+    }
+
+    /// Replaces the current version of `T` with the one computed by `f` from it,
+    /// and returns the new version.
+    ///
+    /// `f` is called without holding the lock guarding `T`. If another version of `T`
+    /// has been published in the meantime, `f` is called again with that version.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`read`](Protected::read) or [`write`](Protected::write) if this user is
+    /// denied access to `T`.
+    pub fn update(&self, f: impl FnMut(&T) -> T) -> Result<Arc<T>, AccessError<Id>> {
+        self.update_checked(f)
+    }
+}
+
+impl<T, A, Id: UserId> Protected<Arc<T>, A, Id> {
+    /// Clones the current version of `T`, if this instance of Protected may read `T`.
+    fn snapshot_checked(&self) -> Result<Arc<T>, AccessError<Id>> {
+        let waiting = Stopwatch::start();
+        // Every write completed before this call has bumped the version already,
+        // so a copy kept for this version is at least as recent as these writes.
+        let version = self.inner.version.load(Ordering::SeqCst);
+        let kept = {
+            let control = self.read_control()?;
+            self.check_access(&control, Operation::Read)
+                .map(|()| self.inner.snapshot.load(version))
+        };
+        match kept {
+            Ok(Some(snapshot)) => {
+                self.report(Operation::Read, waiting, &Ok(()));
+                Ok(snapshot)
+            }
+            Ok(None) => {
+                let (snapshot, version) = {
+                    let guard = self.read_checked(Wait::Forever)?;
+                    // Write guards bump the version once they have released the
+                    // lock, so `guard` is at least as recent as this version.
+                    let version = self.inner.version.load(Ordering::SeqCst);
+                    (Arc::clone(&*guard), version)
+                };
+                self.inner.snapshot.store(snapshot.clone(), version);
+                Ok(snapshot)
+            }
+            Err(error) => {
+                self.report(Operation::Read, waiting, &Err(error.clone()));
+                Err(error)
+            }
+        }
+    }
+
+    /// Replaces the current version of `T`, if this instance of Protected may write `T`.
+    fn publish_checked(&self, value: Arc<T>) -> Result<Arc<T>, AccessError<Id>> {
+        // The previous version is dropped once the lock has been released, and
+        // once it is no longer kept apart for snapshots, in case this was its
+        // last snapshot.
+        let _previous = mem::replace(&mut *self.write_checked(Wait::Forever)?, value.clone());
+        self.inner.snapshot.clear();
+        Ok(value)
+    }
+
+    /// Replaces the current version of `T` with the one computed by `f`, unless
+    /// another version has been published since `f` was called, in which case
+    /// `f` is called again.
+    ///
+    /// Attempts that are retried do not write `T`, so they are neither accounted
+    /// for as writes nor audited, and do not bump the version of `T`.
+    fn update_checked(&self, mut f: impl FnMut(&T) -> T) -> Result<Arc<T>, AccessError<Id>> {
+        loop {
+            let current = self.snapshot_checked()?;
+            let next = Arc::new(f(&current));
+            let waiting = Stopwatch::start();
+            let mut guard = self.lock_write(Wait::Forever, waiting)?;
+            if let Some(published) = &guard.guard.value {
+                if !Arc::ptr_eq(published, &current) {
+                    continue;
+                }
+            }
+            // Access is only checked once `T` is about to be written, which also
+            // reports that `T` has been destroyed in the meantime.
+            self.authorize(&mut guard.release, Operation::Write, waiting)?;
+            guard.release.measure(&self.counters);
+            guard.release.bump(&self.inner.version);
+            let _previous = mem::replace(&mut *guard, next.clone());
+            drop(guard);
+            self.inner.snapshot.clear();
+            return Ok(next);
+        }
+    }
+}
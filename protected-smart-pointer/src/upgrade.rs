@@ -0,0 +1,183 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use crate::lock::{UpgradableReadGuard, WriteGuard};
+use crate::notify::Release;
+use crate::stats::Stopwatch;
+use crate::{
+    AccessError, Operation, Owner, Protected, ProtectedBox, ProtectedReadGuard,
+    ProtectedWriteGuard, User, UserId, Wait, DESTROYED,
+};
+
+/// RAII structure used to release the shared read access of a lock when dropped,
+/// which can be atomically upgraded to exclusive write access.
+///
+/// Other readers may hold guards to `T` alongside this guard, but there is at
+/// most one upgradable read guard to `T` at a time, and no write guard.
+/// This structure is returned by `upgradable_read` and `try_upgradable_read`.
+#[must_use = "if unused the lock will immediately be released"]
+pub struct ProtectedUpgradableReadGuard<'a, T, A, Id: UserId = u32> {
+    guard: UpgradableReadGuard<'a, ProtectedBox<T>>,
+    release: Release<'a>,
+    protected: &'a Protected<T, A, Id>,
+}
+
+impl<T, Id: UserId> Protected<T, Owner, Id> {
+    /// Locks this `T` so that the owner has shared read access to `T`, which can
+    /// later be upgraded to exclusive write access without releasing the lock.
+    ///
+    /// This blocks while `T` is locked for writing, or while another upgradable
+    /// read guard to `T` is held.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lock guarding `T` has been poisoned.
+    pub fn upgradable_read(
+        &self,
+    ) -> Result<ProtectedUpgradableReadGuard<'_, T, Owner, Id>, AccessError<Id>> {
+        self.upgradable_checked(Wait::Forever)
+    }
+
+    /// Attempts to lock this `T` so that the owner has upgradable shared read
+    /// access to `T`, without blocking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::WouldBlock`] if `T` is currently
+    /// locked for writing or another upgradable read guard to `T` is held, or an
+    /// error if the lock guarding `T` has been poisoned.
+    pub fn try_upgradable_read(
+        &self,
+    ) -> Result<ProtectedUpgradableReadGuard<'_, T, Owner, Id>, AccessError<Id>> {
+        self.upgradable_checked(Wait::Never)
+    }
+}
+
+impl<T, Id: UserId> Protected<T, User, Id> {
+    /// Locks this `T` so that this user has shared read access to `T`, which can
+    /// later be upgraded to exclusive write access without releasing the lock.
+    ///
+    /// This blocks while `T` is locked for writing, or while another upgradable
+    /// read guard to `T` is held.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`read`](Protected::read) if this user is denied access to `T`.
+    pub fn upgradable_read(
+        &self,
+    ) -> Result<ProtectedUpgradableReadGuard<'_, T, User, Id>, AccessError<Id>> {
+        self.upgradable_checked(Wait::Forever)
+    }
+
+    /// Attempts to lock this `T` so that this user has upgradable shared read
+    /// access to `T`, without blocking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::WouldBlock`] if `T` is currently
+    /// locked for writing or another upgradable read guard to `T` is held, or any
+    /// of the errors returned by [`read`](Protected::read) if this user is denied
+    /// access to `T`.
+    pub fn try_upgradable_read(
+        &self,
+    ) -> Result<ProtectedUpgradableReadGuard<'_, T, User, Id>, AccessError<Id>> {
+        self.upgradable_checked(Wait::Never)
+    }
+}
+
+impl<T, A, Id: UserId> Protected<T, A, Id> {
+    /// Acquires the inner lock with upgradable shared read access, waiting as long
+    /// as `wait` allows, and checks that this instance of Protected may read `T`.
+    fn upgradable_checked(
+        &self,
+        wait: Wait,
+    ) -> Result<ProtectedUpgradableReadGuard<'_, T, A, Id>, AccessError<Id>> {
+        let waiting = Stopwatch::start();
+        let guard = self.lock_with(wait, waiting, |deadline| {
+            self.inner
+                .lock
+                .upgradable_read(&self.inner.released, deadline)
+        })?;
+        let mut guard = ProtectedUpgradableReadGuard {
+            guard,
+            release: Release::new(&self.inner.released),
+            protected: self,
+        };
+        self.authorize(&mut guard.release, Operation::Read, waiting)?;
+        guard.release.measure(&self.counters);
+        Ok(guard)
+    }
+}
+
+impl<'a, T, A, Id: UserId> ProtectedUpgradableReadGuard<'a, T, A, Id> {
+    /// Upgrades this guard to exclusive write access to `T`, blocking until the
+    /// other readers have released `T`.
+    ///
+    /// No writer can lock `T` in the meantime, so `T` is still as seen through
+    /// this guard once upgraded. Access is checked once more, as the lease of the
+    /// user may have expired, or the user may have been revoked in the meantime.
+    /// This is an associated function that needs to be used as
+    /// `ProtectedUpgradableReadGuard::upgrade(guard)`, so as not to conflict with
+    /// methods of `T`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`write`](Protected::write) if write access is denied, in which case the
+    /// lock is released.
+    pub fn upgrade(orig: Self) -> Result<ProtectedWriteGuard<'a, T, Id>, AccessError<Id>> {
+        let ProtectedUpgradableReadGuard {
+            guard,
+            release,
+            protected,
+        } = orig;
+        let waiting = Stopwatch::start();
+        let guard = UpgradableReadGuard::upgrade(guard, &protected.inner.released);
+        let mut guard = ProtectedWriteGuard {
+            guard,
+            release,
+            _marker: PhantomData,
+        };
+        guard.release.upgrade();
+        protected.authorize(&mut guard.release, Operation::Write, waiting)?;
+        guard.release.bump(&protected.inner.version);
+        Ok(guard)
+    }
+}
+
+impl<'a, T, Id> ProtectedWriteGuard<'a, T, Id> {
+    /// Atomically converts this guard to shared read access to `T`, so that
+    /// other readers can lock `T` right away, but no writer can lock `T` before
+    /// the returned guard is dropped.
+    ///
+    /// This is an associated function that needs to be used as
+    /// `ProtectedWriteGuard::downgrade(guard)`, so as not to conflict with
+    /// methods of `T`.
+    pub fn downgrade(orig: Self) -> ProtectedReadGuard<'a, T, Id> {
+        let ProtectedWriteGuard {
+            guard, mut release, ..
+        } = orig;
+        let guard = WriteGuard::downgrade(guard);
+        release.downgrade();
+        ProtectedReadGuard {
+            guard,
+            release,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, A, Id: UserId> Deref for ProtectedUpgradableReadGuard<'_, T, A, Id> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        self.guard.value.as_ref().expect(DESTROYED)
+    }
+}
+
+impl<T: fmt::Debug, A, Id: UserId> fmt::Debug for ProtectedUpgradableReadGuard<'_, T, A, Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.guard.fmt_value(f)
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::{
+    AccessError, MappedProtectedReadGuard, MappedProtectedWriteGuard, Owner, Permissions,
+    Protected, ProtectedReadGuard, ProtectedWriteGuard, ReadOnlyUser, User, UserAccess, UserId,
+};
+
+/// A user of `T` that only has access to a part of `T`, such as a field.
+///
+/// Views are created by [`Protected::create_view`] and
+/// [`Protected::create_read_only_view`]. Their guards deref to the part of `T`
+/// they are bound to, while their access is controlled and revoked by the owner
+/// exactly like the access of other users, under the same ID.
+pub struct ProtectedView<T, U: ?Sized, Access, Id: UserId = u32> {
+    user: Protected<T, Access, Id>,
+    project: fn(&T) -> &U,
+    project_mut: Option<fn(&mut T) -> &mut U>,
+}
+
+impl<T, Id: UserId> Protected<T, Owner, Id> {
+    /// Creates a user with a given ID and permissions that only has access to the
+    /// part of `T` returned by `project` and `project_mut`.
+    ///
+    /// Both functions must return the same part of `T`, for example
+    /// `|config| &config.network` and `|config| &mut config.network`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return the errors returned by
+    /// [`create_user`](Protected::create_user).
+    pub fn create_view<U: ?Sized>(
+        &self,
+        id: Id,
+        permissions: Permissions,
+        project: fn(&T) -> &U,
+        project_mut: fn(&mut T) -> &mut U,
+    ) -> Result<ProtectedView<T, U, User, Id>, AccessError<Id>> {
+        Ok(ProtectedView {
+            user: self.create_user(id, permissions)?,
+            project,
+            project_mut: Some(project_mut),
+        })
+    }
+
+    /// Creates a user with a given ID that can only read the part of `T`
+    /// returned by `project`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return the errors returned by
+    /// [`create_read_only_user`](Protected::create_read_only_user).
+    pub fn create_read_only_view<U: ?Sized>(
+        &self,
+        id: Id,
+        project: fn(&T) -> &U,
+    ) -> Result<ProtectedView<T, U, ReadOnlyUser, Id>, AccessError<Id>> {
+        Ok(ProtectedView {
+            user: self.create_read_only_user(id)?,
+            project,
+            project_mut: None,
+        })
+    }
+}
+
+impl<T, U: ?Sized, A: UserAccess, Id: UserId> ProtectedView<T, U, A, Id> {
+    /// Locks `T` so that this view has shared read access to its part of `T`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`Protected::read`] for users.
+    pub fn read(&self) -> Result<MappedProtectedReadGuard<'_, T, U, Id>, AccessError<Id>> {
+        Ok(ProtectedReadGuard::map(self.user.read()?, self.project))
+    }
+
+    /// Attempts to lock `T` so that this view has shared read access to its part
+    /// of `T`, without blocking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`Protected::try_read`] for users.
+    pub fn try_read(&self) -> Result<MappedProtectedReadGuard<'_, T, U, Id>, AccessError<Id>> {
+        Ok(ProtectedReadGuard::map(self.user.try_read()?, self.project))
+    }
+
+    /// Locks `T` so that this view has shared read access to its part of `T`,
+    /// blocking for at most `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`Protected::read_timeout`] for users.
+    pub fn read_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<MappedProtectedReadGuard<'_, T, U, Id>, AccessError<Id>> {
+        Ok(ProtectedReadGuard::map(
+            self.user.read_timeout(timeout)?,
+            self.project,
+        ))
+    }
+}
+
+impl<T, U: ?Sized, Id: UserId> ProtectedView<T, U, User, Id> {
+    /// Locks `T` so that this view has exclusive write access to its part of `T`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`Protected::write`] for users.
+    pub fn write(&self) -> Result<MappedProtectedWriteGuard<'_, T, U, Id>, AccessError<Id>> {
+        Ok(ProtectedWriteGuard::map(
+            self.user.write()?,
+            self.project_mut(),
+        ))
+    }
+
+    /// Attempts to lock `T` so that this view has exclusive write access to its
+    /// part of `T`, without blocking.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`Protected::try_write`] for users.
+    pub fn try_write(&self) -> Result<MappedProtectedWriteGuard<'_, T, U, Id>, AccessError<Id>> {
+        Ok(ProtectedWriteGuard::map(
+            self.user.try_write()?,
+            self.project_mut(),
+        ))
+    }
+
+    /// Locks `T` so that this view has exclusive write access to its part of `T`,
+    /// blocking for at most `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`Protected::write_timeout`] for users.
+    pub fn write_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<MappedProtectedWriteGuard<'_, T, U, Id>, AccessError<Id>> {
+        Ok(ProtectedWriteGuard::map(
+            self.user.write_timeout(timeout)?,
+            self.project_mut(),
+        ))
+    }
+
+    fn project_mut(&self) -> fn(&mut T) -> &mut U {
+        let Some(project_mut) = self.project_mut else {
+            unreachable!("views with write access always project mutably");
+        };
+        project_mut
+    }
+}
+
+impl<T, U: ?Sized, A, Id: UserId + fmt::Debug> fmt::Debug for ProtectedView<T, U, A, Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtectedView")
+            .field(
+                "id",
+                &self
+                    .user
+                    .capability
+                    .as_ref()
+                    .map(|capability| &capability.id),
+            )
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use crate::{AccessError, Owner, Protected, ProtectedReadGuard, UserAccess, UserId, Wait};
+
+impl<T, Id: UserId> Protected<T, Owner, Id> {
+    /// Returns the current version of `T`, which is bumped whenever a write guard
+    /// to `T` is dropped.
+    pub fn version(&self) -> u64 {
+        self.inner.version.load(Ordering::SeqCst)
+    }
+
+    /// Blocks until the version of `T` differs from `last_version`, and returns
+    /// the new version.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::Destroyed`] if `T` is destroyed
+    /// while waiting, or an error if the lock guarding `T` has been poisoned.
+    pub fn wait_for_change(&self, last_version: u64) -> Result<u64, AccessError<Id>> {
+        self.changed_checked(last_version, Wait::Forever)
+    }
+
+    /// Blocks for at most `timeout` until the version of `T` differs from
+    /// `last_version`, and returns the new version.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::TimedOut`] if the version of `T`
+    /// is still `last_version` after `timeout`, or any of the errors returned by
+    /// [`wait_for_change`](Protected::wait_for_change).
+    pub fn wait_for_change_timeout(
+        &self,
+        last_version: u64,
+        timeout: Duration,
+    ) -> Result<u64, AccessError<Id>> {
+        self.changed_checked(last_version, Wait::timeout(timeout))
+    }
+
+    /// Blocks until `predicate` holds for `T`, and returns a guard to it.
+    ///
+    /// `predicate` is called with `T` locked for reading, first right away, and
+    /// then every time a write guard to `T` has been dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`read`](Protected::read) or [`wait_for_change`](Protected::wait_for_change).
+    pub fn wait_until(
+        &self,
+        predicate: impl FnMut(&T) -> bool,
+    ) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.wait_until_checked(predicate, Wait::Forever)
+    }
+
+    /// Blocks for at most `timeout` until `predicate` holds for `T`, and returns
+    /// a guard to it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::TimedOut`] if `predicate` still
+    /// does not hold after `timeout`, or any of the errors returned by
+    /// [`wait_until`](Protected::wait_until).
+    pub fn wait_until_timeout(
+        &self,
+        predicate: impl FnMut(&T) -> bool,
+        timeout: Duration,
+    ) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.wait_until_checked(predicate, Wait::timeout(timeout))
+    }
+}
+
+impl<T, A: UserAccess, Id: UserId> Protected<T, A, Id> {
+    /// Returns the current version of `T`, which is bumped whenever a write guard
+    /// to `T` is dropped.
+    pub fn version(&self) -> u64 {
+        self.inner.version.load(Ordering::SeqCst)
+    }
+
+    /// Blocks until the version of `T` differs from `last_version`, and returns
+    /// the new version.
+    ///
+    /// Waiting stops as soon as this user is revoked or the owner of `T` is
+    /// dropped. An expired lease is noticed once `T` or its users change.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the owner of `T` has been dropped,
+    /// if the owner has revoked this user from accessing `T`, if the lease of this
+    /// user has expired, or if the lock guarding `T` has been poisoned.
+    pub fn wait_for_change(&self, last_version: u64) -> Result<u64, AccessError<Id>> {
+        self.changed_checked(last_version, Wait::Forever)
+    }
+
+    /// Blocks for at most `timeout` until the version of `T` differs from
+    /// `last_version`, and returns the new version.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::TimedOut`] if the version of `T`
+    /// is still `last_version` after `timeout`, or any of the errors returned by
+    /// [`wait_for_change`](Protected::wait_for_change).
+    pub fn wait_for_change_timeout(
+        &self,
+        last_version: u64,
+        timeout: Duration,
+    ) -> Result<u64, AccessError<Id>> {
+        self.changed_checked(last_version, Wait::timeout(timeout))
+    }
+
+    /// Blocks until `predicate` holds for `T`, and returns a guard to it.
+    ///
+    /// `predicate` is called with `T` locked for reading, first right away, and
+    /// then every time a write guard to `T` has been dropped. Waiting stops as
+    /// soon as this user is revoked, as in [`wait_for_change`](Protected::wait_for_change).
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`read`](Protected::read) if this user is denied access to `T`.
+    pub fn wait_until(
+        &self,
+        predicate: impl FnMut(&T) -> bool,
+    ) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.wait_until_checked(predicate, Wait::Forever)
+    }
+
+    /// Blocks for at most `timeout` until `predicate` holds for `T`, and returns
+    /// a guard to it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::TimedOut`] if `predicate` still
+    /// does not hold after `timeout`, or any of the errors returned by
+    /// [`wait_until`](Protected::wait_until).
+    pub fn wait_until_timeout(
+        &self,
+        predicate: impl FnMut(&T) -> bool,
+        timeout: Duration,
+    ) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        self.wait_until_checked(predicate, Wait::timeout(timeout))
+    }
+}
+
+impl<T, A, Id: UserId> Protected<T, A, Id> {
+    /// Waits as long as `wait` allows for the version of `T` to differ from
+    /// `last_version`, checking that this instance of Protected still has access
+    /// to `T` every time an exclusive guard is released.
+    fn changed_checked(&self, last_version: u64, wait: Wait) -> Result<u64, AccessError<Id>> {
+        loop {
+            // Versions are bumped before the exclusive guard is counted as
+            // released, so no change can go unnoticed between both loads.
+            let seen = self.inner.released.exclusive_releases();
+            self.check_key(&*self.read_control()?)?;
+            let version = self.inner.version.load(Ordering::SeqCst);
+            if version != last_version {
+                return Ok(version);
+            }
+            self.wait_for_release(seen, wait)?;
+        }
+    }
+
+    /// Waits as long as `wait` allows for `predicate` to hold for `T`, checking
+    /// it every time an exclusive guard is released.
+    fn wait_until_checked(
+        &self,
+        mut predicate: impl FnMut(&T) -> bool,
+        wait: Wait,
+    ) -> Result<ProtectedReadGuard<'_, T, Id>, AccessError<Id>> {
+        loop {
+            let seen = self.inner.released.exclusive_releases();
+            let guard = self.read_checked(wait)?;
+            if predicate(&guard) {
+                return Ok(guard);
+            }
+            drop(guard);
+            self.wait_for_release(seen, wait)?;
+        }
+    }
+
+    /// Waits as long as `wait` allows for an exclusive guard to be released,
+    /// unless one has been released since `seen` was loaded.
+    fn wait_for_release(&self, seen: u64, wait: Wait) -> Result<(), AccessError<Id>> {
+        let released = &self.inner.released;
+        released
+            .wait_until(wait.deadline(), || {
+                (released.exclusive_releases() != seen).then_some(())
+            })
+            .ok_or_else(|| AccessError::TimedOut { id: self.id() })
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Weak};
+
+use crate::stats::SharedCounters;
+use crate::{
+    AccessError, AuditEventKind, Capability, Holders, Owner, Protected, Shared, User, UserAccess,
+    UserId,
+};
+
+/// A handle to a user of `T` that does not keep `T` alive.
+///
+/// Weak handles keep the access of their user, but `T` is dropped once its
+/// owners and all the other handles to it are gone, instead of being kept alive
+/// by forgotten users. Weak handles are created by [`Protected::downgrade_user`],
+/// and have to be upgraded every time they access `T`.
+pub struct WeakProtected<T, Access, Id: UserId = u32> {
+    inner: Weak<Shared<T, Id>>,
+    capability: Capability<Id>,
+    /// Counts the handles sharing the access key of this user, this one included.
+    holders: Arc<Holders>,
+    counters: SharedCounters,
+    _marker: PhantomData<Access>,
+}
+
+impl<T, Id: UserId> Protected<T, Owner, Id> {
+    /// Converts a handle to a user of `T` into a weak handle, which does not
+    /// keep `T` alive.
+    ///
+    /// The user keeps its access to `T`, and gives it up once the weak handle
+    /// is dropped, as it would have when dropping `user`.
+    ///
+    /// # Errors
+    ///
+    /// This function will hand `user` back if it is not a user of the `T` owned
+    /// by this owner.
+    pub fn downgrade_user<A: UserAccess>(
+        &self,
+        user: Protected<T, A, Id>,
+    ) -> Result<WeakProtected<T, A, Id>, Protected<T, A, Id>> {
+        if !Arc::ptr_eq(&self.inner, &user.inner) {
+            return Err(user);
+        }
+        let (Some(capability), Some(holders)) = (user.capability.clone(), user.holders.clone())
+        else {
+            unreachable!("users always hold a capability");
+        };
+        // The weak handle holds the key of `user` before `user` is dropped, so
+        // that the key is not revoked in between.
+        holders.handles.fetch_add(1, Ordering::SeqCst);
+        self.inner.audit.record(|| AuditEventKind::UserDowngraded {
+            id: capability.id.clone(),
+        });
+        Ok(WeakProtected {
+            inner: Arc::downgrade(&user.inner),
+            capability,
+            holders,
+            counters: user.counters.clone(),
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T, A: UserAccess, Id: UserId> WeakProtected<T, A, Id> {
+    /// Returns a handle to this user that keeps `T` alive until it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AccessError::Destroyed`] if `T` has been dropped,
+    /// or any of the errors returned by [`Protected::read`] for users if this user
+    /// is denied access to `T`.
+    pub fn upgrade(&self) -> Result<Protected<T, A, Id>, AccessError<Id>> {
+        let inner = self.inner.upgrade().ok_or_else(|| AccessError::Destroyed {
+            id: Some(self.capability.id.clone()),
+        })?;
+        // The handle is counted before anything can fail, since dropping it
+        // gives it up again.
+        self.holders.handles.fetch_add(1, Ordering::SeqCst);
+        let user = Protected {
+            inner,
+            capability: Some(self.capability.clone()),
+            holders: Some(self.holders.clone()),
+            counters: self.counters.clone(),
+            _marker: PhantomData,
+        };
+        user.check_key(&*user.read_control()?)?;
+        Ok(user)
+    }
+
+    /// Upgrades this handle, and calls `f` with shared read access to `T`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`upgrade`](WeakProtected::upgrade) or [`Protected::read`] for users.
+    pub fn read<R>(&self, f: impl FnOnce(&T) -> R) -> Result<R, AccessError<Id>> {
+        let user = self.upgrade()?;
+        let result = f(&*user.read()?);
+        Ok(result)
+    }
+}
+
+impl<T, Id: UserId> WeakProtected<T, User, Id> {
+    /// Upgrades this handle, and calls `f` with exclusive write access to `T`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return any of the errors returned by
+    /// [`upgrade`](WeakProtected::upgrade) or [`Protected::write`] for users.
+    pub fn write<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, AccessError<Id>> {
+        let user = self.upgrade()?;
+        let result = f(&mut *user.write()?);
+        Ok(result)
+    }
+}
+
+impl<T, A, Id: UserId> Drop for WeakProtected<T, A, Id> {
+    fn drop(&mut self) {
+        // As in `Drop` for `Protected`, only the last handle takes the lock,
+        // and poisoning is ignored.
+        if self.holders.handles.fetch_sub(1, Ordering::SeqCst) > 1 {
+            return;
+        }
+        let Some(inner) = self.inner.upgrade() else {
+            return;
+        };
+        inner.release_key(&mut inner.control_mut(), &self.capability);
+    }
+}
+
+impl<T, A, Id: UserId + fmt::Debug> fmt::Debug for WeakProtected<T, A, Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeakProtected")
+            .field("id", &self.capability.id)
+            .finish_non_exhaustive()
+    }
+}